@@ -0,0 +1,428 @@
+//! The module holding the `AuthProvider` enum used by legacy auth-provider
+//! users
+
+use crate::get::{get_remainder, get_string, Kind};
+use crate::set::{set_extra, set_mapping, set_string};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `auth-provider` stanza of a user. This is the legacy way of
+/// configuring OIDC, GCP and Azure logins, superseded by exec plugins.
+///
+/// The well known providers are parsed into typed variants, any other provider
+/// is kept as is in the `Other` variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthProvider {
+    /// The `oidc` auth-provider
+    Oidc(OidcConfig),
+
+    /// The `gcp` auth-provider
+    Gcp(GcpConfig),
+
+    /// The `azure` auth-provider
+    Azure(AzureConfig),
+
+    /// Any other auth-provider
+    Other {
+        /// The name of the auth-provider
+        name: String,
+
+        /// The raw `config` mapping of the auth-provider
+        config: Mapping,
+    },
+}
+
+/// The config of the `oidc` auth-provider
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OidcConfig {
+    /// The client id registered with the identity provider
+    pub client_id: Option<String>,
+
+    /// The client secret registered with the identity provider
+    pub client_secret: Option<String>,
+
+    /// The url of the identity provider, used to discover its token endpoint
+    pub idp_issuer_url: Option<String>,
+
+    /// The ID token used as the bearer token
+    pub id_token: Option<String>,
+
+    /// The refresh token used to get a new ID token when it expires
+    pub refresh_token: Option<String>,
+
+    /// A path to the certificate authority of the identity provider
+    pub idp_certificate_authority: Option<PathBuf>,
+
+    /// The base64 encoded certificate authority of the identity provider
+    pub idp_certificate_authority_data: Option<String>,
+
+    /// Any keys in the `config` mapping that are not known to this crate
+    pub extra: Mapping,
+}
+
+/// The config of the `gcp` auth-provider
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcpConfig {
+    /// The cached access token used as the bearer token
+    pub access_token: Option<String>,
+
+    /// When the cached access token expires
+    pub expiry: Option<String>,
+
+    /// The command used to get a new access token
+    pub cmd_path: Option<String>,
+
+    /// The arguments of `cmd_path`
+    pub cmd_args: Option<String>,
+
+    /// The json path of the access token in the output of `cmd_path`
+    pub token_key: Option<String>,
+
+    /// The json path of the expiry in the output of `cmd_path`
+    pub expiry_key: Option<String>,
+
+    /// Any keys in the `config` mapping that are not known to this crate
+    pub extra: Mapping,
+}
+
+/// The config of the `azure` auth-provider
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AzureConfig {
+    /// The cached access token used as the bearer token
+    pub access_token: Option<String>,
+
+    /// The refresh token used to get a new access token
+    pub refresh_token: Option<String>,
+
+    /// When the cached access token expires, as a unix timestamp
+    pub expires_on: Option<String>,
+
+    /// The lifetime of the access token in seconds
+    pub expires_in: Option<String>,
+
+    /// The Azure AD tenant
+    pub tenant_id: Option<String>,
+
+    /// The application id of the client
+    pub client_id: Option<String>,
+
+    /// The application id of the API server
+    pub apiserver_id: Option<String>,
+
+    /// The Azure environment, i.e. "AzurePublicCloud"
+    pub environment: Option<String>,
+
+    /// Any keys in the `config` mapping that are not known to this crate
+    pub extra: Mapping,
+}
+
+/// The keys of the `oidc` config mapping that are parsed into typed fields
+const OIDC_KEYS: &[(&str, Kind)] = &[
+    ("client-id", Kind::String),
+    ("client-secret", Kind::String),
+    ("idp-issuer-url", Kind::String),
+    ("id-token", Kind::String),
+    ("refresh-token", Kind::String),
+    ("idp-certificate-authority", Kind::String),
+    ("idp-certificate-authority-data", Kind::String),
+];
+
+/// The keys of the `gcp` config mapping that are parsed into typed fields
+const GCP_KEYS: &[(&str, Kind)] = &[
+    ("access-token", Kind::String),
+    ("expiry", Kind::String),
+    ("cmd-path", Kind::String),
+    ("cmd-args", Kind::String),
+    ("token-key", Kind::String),
+    ("expiry-key", Kind::String),
+];
+
+/// The keys of the `azure` config mapping that are parsed into typed fields
+const AZURE_KEYS: &[(&str, Kind)] = &[
+    ("access-token", Kind::String),
+    ("refresh-token", Kind::String),
+    ("expires-on", Kind::String),
+    ("expires-in", Kind::String),
+    ("tenant-id", Kind::String),
+    ("client-id", Kind::String),
+    ("apiserver-id", Kind::String),
+    ("environment", Kind::String),
+];
+
+impl AuthProvider {
+    /// The name of the auth-provider, i.e. "oidc"
+    pub fn name(&self) -> &str {
+        match self {
+            AuthProvider::Oidc(_) => "oidc",
+            AuthProvider::Gcp(_) => "gcp",
+            AuthProvider::Azure(_) => "azure",
+            AuthProvider::Other { name, .. } => name,
+        }
+    }
+
+    /// The bearer token to send to the cluster, if the auth-provider has one.
+    ///
+    /// This is the `id-token` for `oidc` and the `access-token` for `gcp` and
+    /// `azure`. For other providers the `access-token` or `id-token` key of
+    /// the config is used.
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            AuthProvider::Oidc(config) => config.id_token.as_deref(),
+            AuthProvider::Gcp(config) => config.access_token.as_deref(),
+            AuthProvider::Azure(config) => config.access_token.as_deref(),
+            AuthProvider::Other { config, .. } => ["access-token", "id-token"]
+                .iter()
+                .find_map(|key| config.get(&Value::String(key.to_string())))
+                .and_then(Value::as_str),
+        }
+    }
+}
+
+impl OidcConfig {
+    /// Whether the ID token is missing, expired or can not be parsed as a
+    /// JWT. In all of these cases the token should be refreshed.
+    pub fn id_token_expired(&self) -> bool {
+        let expires_at = self.id_token.as_deref().and_then(|token| {
+            let payload = token.split('.').nth(1)?;
+            let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+            let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+            claims["exp"].as_u64()
+        });
+
+        match expires_at {
+            Some(expires_at) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|now| now.as_secs())
+                    .unwrap_or_default();
+                now >= expires_at
+            }
+            None => true,
+        }
+    }
+
+    /// Uses the refresh token to get new tokens from the token endpoint of the
+    /// identity provider, and stores them in this config. The token endpoint
+    /// is discovered from the `idp-issuer-url`.
+    #[cfg(feature = "oidc")]
+    pub fn refresh(&mut self) -> crate::errors::Result<()> {
+        use crate::errors::*;
+
+        let refresh_token = self
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| ErrorKind::OidcRefresh("no refresh-token is set".to_string()))?;
+        let issuer = self
+            .idp_issuer_url
+            .as_deref()
+            .ok_or_else(|| ErrorKind::OidcRefresh("no idp-issuer-url is set".to_string()))?;
+        let client_id = self
+            .client_id
+            .as_deref()
+            .ok_or_else(|| ErrorKind::OidcRefresh("no client-id is set".to_string()))?;
+
+        let agent = self.agent()?;
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let discovery: serde_json::Value = agent
+            .get(&discovery_url)
+            .call()
+            .map_err(Box::new)
+            .chain_err(|| ErrorKind::OidcRefresh(format!("could not get {}", discovery_url)))?
+            .into_json()?;
+        let token_endpoint = discovery["token_endpoint"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::OidcRefresh("issuer has no token_endpoint".to_string()))?;
+
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let tokens: serde_json::Value = agent
+            .post(token_endpoint)
+            .send_form(&form)
+            .map_err(Box::new)
+            .chain_err(|| ErrorKind::OidcRefresh(format!("could not post {}", token_endpoint)))?
+            .into_json()?;
+
+        let id_token = tokens["id_token"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::OidcRefresh("response has no id_token".to_string()))?;
+        self.id_token = Some(id_token.to_string());
+        // The refresh token is only rotated by some identity providers
+        if let Some(refresh_token) = tokens["refresh_token"].as_str() {
+            self.refresh_token = Some(refresh_token.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// An HTTP agent trusting the certificate authority of the identity
+    /// provider, if one is configured.
+    #[cfg(feature = "oidc")]
+    fn agent(&self) -> crate::errors::Result<ureq::Agent> {
+        use crate::data::read_data_or_file;
+        use crate::errors::*;
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::CertificateDer;
+        use std::sync::Arc;
+
+        let ca = read_data_or_file(
+            "idp-certificate-authority",
+            self.idp_certificate_authority_data.as_deref(),
+            self.idp_certificate_authority.as_deref(),
+        )?;
+        let ca = match ca {
+            Some(ca) => ca,
+            None => return Ok(ureq::Agent::new()),
+        };
+
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(&ca) {
+            let cert = cert.chain_err(|| {
+                ErrorKind::OidcRefresh("invalid idp certificate authority".to_string())
+            })?;
+            roots.add(cert).chain_err(|| {
+                ErrorKind::OidcRefresh("invalid idp certificate authority".to_string())
+            })?;
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let tls_config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .chain_err(|| ErrorKind::OidcRefresh("could not set up tls".to_string()))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(ureq::AgentBuilder::new()
+            .tls_config(Arc::new(tls_config))
+            .build())
+    }
+}
+
+impl<'de> Deserialize<'de> for AuthProvider {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let map: Mapping = Deserialize::deserialize(d)?;
+        let name = get_string::<D::Error>(&map, "name")?;
+        let config = match map.get(&Value::String("config".to_string())) {
+            Some(Value::Mapping(config)) => config.clone(),
+            Some(Value::Null) | None => Mapping::new(),
+            Some(_) => return Err(D::Error::custom("auth-provider config is not a mapping")),
+        };
+        let get = |key| get_string::<D::Error>(&config, key).ok();
+
+        Ok(match name.as_str() {
+            "oidc" => AuthProvider::Oidc(OidcConfig {
+                client_id: get("client-id"),
+                client_secret: get("client-secret"),
+                idp_issuer_url: get("idp-issuer-url"),
+                id_token: get("id-token"),
+                refresh_token: get("refresh-token"),
+                idp_certificate_authority: get("idp-certificate-authority").map(PathBuf::from),
+                idp_certificate_authority_data: get("idp-certificate-authority-data"),
+                extra: get_remainder(&config, OIDC_KEYS),
+            }),
+            "gcp" => AuthProvider::Gcp(GcpConfig {
+                access_token: get("access-token"),
+                expiry: get("expiry"),
+                cmd_path: get("cmd-path"),
+                cmd_args: get("cmd-args"),
+                token_key: get("token-key"),
+                expiry_key: get("expiry-key"),
+                extra: get_remainder(&config, GCP_KEYS),
+            }),
+            "azure" => AuthProvider::Azure(AzureConfig {
+                access_token: get("access-token"),
+                refresh_token: get("refresh-token"),
+                expires_on: get("expires-on"),
+                expires_in: get("expires-in"),
+                tenant_id: get("tenant-id"),
+                client_id: get("client-id"),
+                apiserver_id: get("apiserver-id"),
+                environment: get("environment"),
+                extra: get_remainder(&config, AZURE_KEYS),
+            }),
+            _ => AuthProvider::Other { name, config },
+        })
+    }
+}
+
+impl Serialize for AuthProvider {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut config = Mapping::new();
+        let mut set = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                set_string(&mut config, key, value);
+            }
+        };
+
+        let extra = match self {
+            AuthProvider::Oidc(oidc) => {
+                set("client-id", &oidc.client_id);
+                set("client-secret", &oidc.client_secret);
+                set("id-token", &oidc.id_token);
+                set(
+                    "idp-certificate-authority",
+                    &oidc
+                        .idp_certificate_authority
+                        .as_ref()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                );
+                set(
+                    "idp-certificate-authority-data",
+                    &oidc.idp_certificate_authority_data,
+                );
+                set("idp-issuer-url", &oidc.idp_issuer_url);
+                set("refresh-token", &oidc.refresh_token);
+                &oidc.extra
+            }
+            AuthProvider::Gcp(gcp) => {
+                set("access-token", &gcp.access_token);
+                set("cmd-args", &gcp.cmd_args);
+                set("cmd-path", &gcp.cmd_path);
+                set("expiry", &gcp.expiry);
+                set("expiry-key", &gcp.expiry_key);
+                set("token-key", &gcp.token_key);
+                &gcp.extra
+            }
+            AuthProvider::Azure(azure) => {
+                set("access-token", &azure.access_token);
+                set("apiserver-id", &azure.apiserver_id);
+                set("client-id", &azure.client_id);
+                set("environment", &azure.environment);
+                set("expires-in", &azure.expires_in);
+                set("expires-on", &azure.expires_on);
+                set("refresh-token", &azure.refresh_token);
+                set("tenant-id", &azure.tenant_id);
+                &azure.extra
+            }
+            AuthProvider::Other { config: other, .. } => other,
+        };
+        set_extra(&mut config, extra);
+
+        let mut map = Mapping::new();
+        if !config.is_empty() {
+            set_mapping(&mut map, "config", config);
+        }
+        set_string(&mut map, "name", self.name());
+        map.serialize(s)
+    }
+}
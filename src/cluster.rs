@@ -1,16 +1,23 @@
 //! The module holding the `Cluster` struct
 
-use crate::get::{get_bool, get_mapping, get_string};
-use serde::{Deserialize, Deserializer};
+use crate::data::read_data_or_file;
+use crate::extension::Extensions;
+use crate::get::{get_bool, get_mapping, get_optional, get_remainder, get_string, Kind};
+use crate::set::{set_bool, set_extra, set_mapping, set_path, set_serialized, set_string};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Mapping;
 use std::path::PathBuf;
+use url::Url;
+
+/// The proxy schemes that kubectl supports
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks5"];
 
 /// A cluster represents a cluster that the user knows how to connect to.
 ///
 /// Note: The cluster struct is flattened when compared to its representation in
 /// the yaml file. There is no `cluster` mapping, the values of the `cluster`
 /// mapping are directly accessible on the `Cluster` struct.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Cluster {
     /// The name given to the cluster by the user
     pub name: String,
@@ -29,6 +36,78 @@ pub struct Cluster {
     /// When set to true this is a signal that any certificate checking should
     /// be bypassed by the user agent.
     pub insecure_skip_tls_verify: bool,
+
+    /// The proxy to use for all requests to the cluster, as written in the
+    /// config. Use [`proxy`](#method.proxy) to get it parsed.
+    pub proxy_url: Option<String>,
+
+    /// The name to verify the certificate of the server against, instead of
+    /// the host name of `server`
+    pub tls_server_name: Option<String>,
+
+    /// When set to true responses from the server should not be compressed
+    pub disable_compression: bool,
+
+    /// Extensions that other tools have stored on this cluster
+    pub extensions: Extensions,
+
+    /// Any keys in the `cluster` mapping that are not known to this crate.
+    /// These are kept so that no data is lost when the config is written back.
+    pub extra: Mapping,
+
+    /// Any keys next to `name` and `cluster` that are not known to this crate,
+    /// kept for the same reason as `extra`
+    pub entry_extra: Mapping,
+}
+
+/// The keys of the `cluster` mapping that are parsed into typed fields
+const CLUSTER_KEYS: &[(&str, Kind)] = &[
+    ("server", Kind::String),
+    ("certificate-authority", Kind::String),
+    ("certificate-authority-data", Kind::String),
+    ("insecure-skip-tls-verify", Kind::Bool),
+    ("proxy-url", Kind::String),
+    ("tls-server-name", Kind::String),
+    ("disable-compression", Kind::Bool),
+    ("extensions", Kind::Other),
+];
+
+impl Cluster {
+    /// Gets the PEM encoded certificate authority of the cluster, either by
+    /// decoding `certificate-authority-data` or by reading the
+    /// `certificate-authority` file.
+    ///
+    /// Returns `None` when neither is set, and an error when both are set.
+    pub fn ca_pem(&self) -> crate::errors::Result<Option<Vec<u8>>> {
+        read_data_or_file(
+            "certificate-authority",
+            self.certificate_authority_data.as_deref(),
+            self.certificate_authority.as_deref(),
+        )
+    }
+
+    /// Parses the `proxy-url` of the cluster, only accepting the `http`,
+    /// `https` and `socks5` schemes that kubectl supports.
+    ///
+    /// Returns `None` when no proxy is set, and an error when it is not valid.
+    pub fn proxy(&self) -> crate::errors::Result<Option<Url>> {
+        use crate::errors::{ErrorKind, ResultExt};
+
+        let proxy_url = match &self.proxy_url {
+            Some(proxy_url) => proxy_url,
+            None => return Ok(None),
+        };
+        let url =
+            Url::parse(proxy_url).chain_err(|| ErrorKind::InvalidUrl(proxy_url.to_string()))?;
+        if !PROXY_SCHEMES.contains(&url.scheme()) {
+            bail!(
+                "unsupported proxy-url scheme '{}', expected one of {}",
+                url.scheme(),
+                PROXY_SCHEMES.join(", ")
+            );
+        }
+        Ok(Some(url))
+    }
 }
 
 impl<'de> Deserialize<'de> for Cluster {
@@ -38,6 +117,7 @@ impl<'de> Deserialize<'de> for Cluster {
     {
         let map: Mapping = Deserialize::deserialize(d)?;
         let name = get_string(&map, "name")?;
+        let entry_extra = get_remainder(&map, &[("name", Kind::Other), ("cluster", Kind::Other)]);
         let cluster = get_mapping(map, "cluster")?;
 
         Ok(Cluster {
@@ -53,12 +133,63 @@ impl<'de> Deserialize<'de> for Cluster {
             .ok(),
             insecure_skip_tls_verify: get_bool::<D::Error>(&cluster, "insecure-skip-tls-verify")
                 .unwrap_or_default(),
+            proxy_url: get_string::<D::Error>(&cluster, "proxy-url").ok(),
+            tls_server_name: get_string::<D::Error>(&cluster, "tls-server-name").ok(),
+            disable_compression: get_bool::<D::Error>(&cluster, "disable-compression")
+                .unwrap_or_default(),
+            extensions: get_optional(&cluster, "extensions")?.unwrap_or_default(),
+            extra: get_remainder(&cluster, CLUSTER_KEYS),
+            entry_extra,
         })
 
         // Cluster::try_from(map)
     }
 }
 
+impl Serialize for Cluster {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut cluster = Mapping::new();
+        if let Some(certificate_authority) = &self.certificate_authority {
+            set_path::<S::Error>(&mut cluster, "certificate-authority", certificate_authority)?;
+        }
+        if let Some(certificate_authority_data) = &self.certificate_authority_data {
+            set_string(
+                &mut cluster,
+                "certificate-authority-data",
+                certificate_authority_data,
+            );
+        }
+        if self.disable_compression {
+            set_bool(&mut cluster, "disable-compression", true);
+        }
+        if !self.extensions.is_empty() {
+            set_serialized::<_, S::Error>(&mut cluster, "extensions", &self.extensions)?;
+        }
+        if self.insecure_skip_tls_verify {
+            set_bool(&mut cluster, "insecure-skip-tls-verify", true);
+        }
+        if let Some(proxy_url) = &self.proxy_url {
+            set_string(&mut cluster, "proxy-url", proxy_url);
+        }
+        set_string(&mut cluster, "server", &self.server);
+        if let Some(tls_server_name) = &self.tls_server_name {
+            set_string(&mut cluster, "tls-server-name", tls_server_name);
+        }
+        set_extra(&mut cluster, &self.extra);
+
+        let mut map = Mapping::new();
+        set_string(&mut map, "name", &self.name);
+        set_mapping(&mut map, "cluster", cluster);
+        for (key, value) in &self.entry_extra {
+            map.insert(key.clone(), value.clone());
+        }
+        map.serialize(s)
+    }
+}
+
 /*
 TODO Write a working TryFrom impl
 
@@ -1,8 +1,10 @@
 //! The module holding the `Context` struct
 
-use crate::get::{get_mapping, get_string};
+use crate::extension::Extensions;
+use crate::get::{get_mapping, get_optional, get_remainder, get_string, Kind};
+use crate::set::{set_extra, set_mapping, set_serialized, set_string};
 use crate::{Cluster, Config, User};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Mapping;
 
 /// A context represents a mapping between known users in the `users` set and
@@ -11,7 +13,7 @@ use serde_yaml::Mapping;
 /// Note: The context struct is flattened when compared to its representation in
 /// the yaml file. There is no `context` mapping, the values of the `context`
 /// mapping are directly accessible on the `Context` struct.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Context {
     /// The name given to this context by the user
     pub name: String,
@@ -24,31 +26,46 @@ pub struct Context {
 
     /// The user `name` this cluster refers to
     pub user: String,
+
+    /// Extensions that other tools have stored on this context
+    pub extensions: Extensions,
+
+    /// Any keys in the `context` mapping that are not known to this crate.
+    /// These are kept so that no data is lost when the config is written back.
+    pub extra: Mapping,
+
+    /// Any keys next to `name` and `context` that are not known to this crate,
+    /// kept for the same reason as `extra`
+    pub entry_extra: Mapping,
 }
 
+/// The keys of the `context` mapping that are parsed into typed fields
+const CONTEXT_KEYS: &[(&str, Kind)] = &[
+    ("cluster", Kind::Other),
+    ("user", Kind::Other),
+    ("namespace", Kind::String),
+    ("extensions", Kind::Other),
+];
+
 impl Context {
     /// Gets the `Cluster` object associated with this context if it exists in
     /// the provided config object.
+    ///
+    /// This clones the cluster, use
+    /// [`Config::cluster`](../struct.Config.html#method.cluster) to borrow it
+    /// instead.
     pub fn get_cluster(&self, config: &Config) -> Option<Cluster> {
-        for cluster in config.clusters.iter() {
-            if &cluster.name == &self.cluster {
-                return Some(cluster.clone());
-            }
-        }
-
-        None
+        config.cluster(&self.cluster).cloned()
     }
 
     /// Gets the `User` object associated with this context if it exists in the
     /// provided config object.
+    ///
+    /// This clones the user, use
+    /// [`Config::user`](../struct.Config.html#method.user) to borrow it
+    /// instead.
     pub fn get_user(&self, config: &Config) -> Option<User> {
-        for user in config.users.iter() {
-            if user.name == self.user {
-                return Some(user.clone());
-            }
-        }
-
-        None
+        config.user(&self.user).cloned()
     }
 }
 
@@ -59,6 +76,7 @@ impl<'de> Deserialize<'de> for Context {
     {
         let map: Mapping = Deserialize::deserialize(d)?;
         let name = get_string(&map, "name")?;
+        let entry_extra = get_remainder(&map, &[("name", Kind::Other), ("context", Kind::Other)]);
         let context = get_mapping(map, "context")?;
 
         Ok(Context {
@@ -66,12 +84,41 @@ impl<'de> Deserialize<'de> for Context {
             cluster: get_string::<D::Error>(&context, "cluster")?,
             user: get_string::<D::Error>(&context, "user")?,
             namespace: get_string::<D::Error>(&context, "namespace").ok(),
+            extensions: get_optional(&context, "extensions")?.unwrap_or_default(),
+            extra: get_remainder(&context, CONTEXT_KEYS),
+            entry_extra,
         })
 
         // Context::try_from(map)
     }
 }
 
+impl Serialize for Context {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut context = Mapping::new();
+        set_string(&mut context, "cluster", &self.cluster);
+        if !self.extensions.is_empty() {
+            set_serialized::<_, S::Error>(&mut context, "extensions", &self.extensions)?;
+        }
+        if let Some(namespace) = &self.namespace {
+            set_string(&mut context, "namespace", namespace);
+        }
+        set_string(&mut context, "user", &self.user);
+        set_extra(&mut context, &self.extra);
+
+        let mut map = Mapping::new();
+        set_string(&mut map, "name", &self.name);
+        set_mapping(&mut map, "context", context);
+        for (key, value) in &self.entry_extra {
+            map.insert(key.clone(), value.clone());
+        }
+        map.serialize(s)
+    }
+}
+
 /*
 TODO Write a working TryFrom impl
 
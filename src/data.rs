@@ -0,0 +1,31 @@
+use crate::errors::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fs::read;
+use std::path::Path;
+
+/// Gets the bytes of a credential that is either given inline as base64 in
+/// `<key>-data`, or as a path to a file in `<key>`.
+pub fn read_data_or_file(
+    key: &str,
+    data: Option<&str>,
+    path: Option<&Path>,
+) -> Result<Option<Vec<u8>>> {
+    match (data, path) {
+        (Some(_), Some(_)) => bail!(ErrorKind::ConflictingCredentials(key.to_string())),
+        (Some(data), None) => {
+            // Whitespace is allowed in kubeconfigs, i.e. when the data is wrapped
+            let data: String = data.split_whitespace().collect();
+            let bytes = STANDARD
+                .decode(data)
+                .chain_err(|| ErrorKind::InvalidBase64(format!("{}-data", key)))?;
+            Ok(Some(bytes))
+        }
+        (None, Some(path)) => {
+            let bytes = read(path)
+                .chain_err(|| ErrorKind::UnreadableFile(format!("{}", path.display())))?;
+            Ok(Some(bytes))
+        }
+        (None, None) => Ok(None),
+    }
+}
@@ -0,0 +1,795 @@
+//! Editing a kubeconfig file in place, keeping its comments, key order and
+//! formatting.
+//!
+//! Saving a [`Config`](../struct.Config.html) writes the whole file again, which
+//! drops comments and puts the keys in the order this crate writes them. A
+//! [`ConfigDocument`](struct.ConfigDocument.html) instead changes only the lines
+//! an edit is about:
+//!
+//! ```
+//! use kube_conf::document::ConfigDocument;
+//!
+//! let mut document = ConfigDocument::load("tests/config-comments.yml")?;
+//! document.set_namespace("exp-scratch", "default")?;
+//! document.use_context("exp-scratch")?;
+//!
+//! let text = document.to_yaml_string();
+//! assert!(text.contains("    context:\n      cluster: scratch\n      namespace: default\n"));
+//! assert!(text.contains("current-context: exp-scratch  # the default\n"));
+//! assert!(text.contains("# Throwaway cluster for experiments\n"));
+//! # Ok::<(), kube_conf::errors::Error>(())
+//! ```
+//!
+//! The editing works on the lines of the file and expects the block style
+//! that `kubectl` writes. Edits to parts of a file written in flow style, like
+//! `context: {cluster: a, user: b}`, return an error instead.
+
+use crate::errors::*;
+use crate::lock::{write_atomic, FileLock};
+use crate::yaml;
+use crate::{Cluster, Config, Context, User};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// A kubeconfig file that is edited as text. See the
+/// [module documentation](index.html).
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+    path: Option<PathBuf>,
+    /// The text of the file when it was loaded or last saved
+    loaded: Option<String>,
+    lines: Vec<String>,
+    config: Config,
+}
+
+/// A top level key and the lines of its value
+struct Section {
+    key_line: usize,
+    /// The line after the last line of the value that is not a comment
+    end: usize,
+}
+
+/// A block mapping, from `start` to `end`, with its keys at `key_indent`
+struct Block {
+    start: usize,
+    end: usize,
+    key_indent: usize,
+    /// The indentation of the `-` when the block is an entry of a list
+    dash: Option<usize>,
+}
+
+/// A key of a block mapping and the lines of its value
+struct Key {
+    name: String,
+    line: usize,
+    /// The line after the last line of the value that is not a comment
+    end: usize,
+    /// Everything on the line before the key
+    prefix: String,
+    /// Everything after the colon
+    rest: String,
+}
+
+/// An entry of a list under a top level key
+struct Item {
+    /// The first of the comment lines right above the entry
+    comments_start: usize,
+    start: usize,
+    end: usize,
+    /// The indentation of the `-`
+    indent: usize,
+    /// The indentation of the keys of the entry
+    key_indent: usize,
+}
+
+impl ConfigDocument {
+    /// Reads the kubeconfig file at the given path for editing. Paths in it
+    /// are kept as they are written, like
+    /// [`Config::load_raw`](../struct.Config.html#method.load_raw).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ConfigDocument> {
+        let text = read_to_string(&path)
+            .chain_err(|| ErrorKind::MissingConfigFile(format!("{}", path.as_ref().display())))?;
+        let mut document = ConfigDocument::parse(&text)?;
+        document.path = Some(path.as_ref().to_path_buf());
+        document.loaded = Some(text);
+        Ok(document)
+    }
+
+    /// Reads kubeconfig yaml for editing.
+    pub fn parse(text: &str) -> Result<ConfigDocument> {
+        Ok(ConfigDocument {
+            path: None,
+            loaded: None,
+            lines: text.split('\n').map(str::to_string).collect(),
+            config: parse_config(text)?,
+        })
+    }
+
+    /// The config as it is after the edits made so far
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The text of the file as it is after the edits made so far
+    pub fn to_yaml_string(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Sets `current-context` to the given context, which must exist.
+    pub fn use_context(&mut self, name: &str) -> Result<()> {
+        if self.config.context(name).is_none() {
+            bail!(ErrorKind::UnknownContext(name.to_string()));
+        }
+
+        let value = render_scalar(name)?;
+        let mut lines = self.lines.clone();
+        match find_section(&lines, "current-context") {
+            Some(section) => {
+                lines[section.key_line] =
+                    with_value(&lines[section.key_line], 0, "current-context", &value);
+                lines.drain(section.key_line + 1..section.end);
+            }
+            None => {
+                let at = find_section(&lines, "contexts")
+                    .map(|s| s.end)
+                    .unwrap_or_else(|| end_of_document(&lines));
+                lines.insert(at, format!("current-context: {}", value));
+            }
+        }
+        self.apply(lines)
+    }
+
+    /// Sets the namespace of the given context, adding the key next to the
+    /// other keys of the context if it is not there yet.
+    pub fn set_namespace(&mut self, context: &str, namespace: &str) -> Result<()> {
+        let value = render_scalar(namespace)?;
+        let mut lines = self.lines.clone();
+        let item = match find_item(&lines, "contexts", context)? {
+            Some((_, item, _)) => item,
+            None => bail!(ErrorKind::UnknownContext(context.to_string())),
+        };
+
+        let (key_line, rest) = match item_key(&lines, &item, "context") {
+            Some(found) => found,
+            None => bail!("context '{}' has no 'context' key", context),
+        };
+        if !strip_comment(&rest).is_empty() {
+            bail!("context '{}' can not be edited in place", context);
+        }
+
+        // The keys of the context are the lines indented further than it
+        let mut child_indent = None;
+        let mut children_end = key_line + 1;
+        for (i, line) in lines.iter().enumerate().take(item.end).skip(key_line + 1) {
+            if !is_content(line) {
+                continue;
+            }
+            if indent(line) <= item.key_indent {
+                break;
+            }
+            child_indent.get_or_insert(indent(line));
+            children_end = i + 1;
+        }
+        let child_indent = child_indent.unwrap_or(item.key_indent + 2);
+
+        let mut insert_at = children_end;
+        for i in key_line + 1..children_end {
+            let line = &lines[i];
+            if !is_content(line) || indent(line) != child_indent {
+                continue;
+            }
+            match split_key(line.trim_start()) {
+                Some(("namespace", _)) => {
+                    lines[i] = with_value(line, child_indent, "namespace", &value);
+                    return self.apply(lines);
+                }
+                Some((key, _)) if key > "namespace" && insert_at == children_end => {
+                    insert_at = i;
+                }
+                _ => {}
+            }
+        }
+        lines.insert(
+            insert_at,
+            format!("{}namespace: {}", " ".repeat(child_indent), value),
+        );
+        self.apply(lines)
+    }
+
+    /// Adds the cluster after the other clusters, or replaces the cluster with
+    /// the same name, like [`Config::replace_cluster`](../struct.Config.html#method.replace_cluster).
+    /// Only the keys whose values differ are changed in the file. Returns the
+    /// cluster that was replaced.
+    pub fn replace_cluster(&mut self, cluster: Cluster) -> Result<Option<Cluster>> {
+        let existing = self.config.cluster(&cluster.name).cloned();
+        self.replace_entry("clusters", &cluster.name, &cluster, existing)
+    }
+
+    /// Adds the context after the other contexts, or replaces the context with
+    /// the same name, like [`Config::replace_context`](../struct.Config.html#method.replace_context).
+    /// Only the keys whose values differ are changed in the file. Returns the
+    /// context that was replaced.
+    pub fn replace_context(&mut self, context: Context) -> Result<Option<Context>> {
+        let existing = self.config.context(&context.name).cloned();
+        self.replace_entry("contexts", &context.name, &context, existing)
+    }
+
+    /// Adds the user after the other users, or replaces the user with the same
+    /// name, like [`Config::replace_user`](../struct.Config.html#method.replace_user).
+    /// Only the keys whose values differ are changed in the file. Returns the
+    /// user that was replaced.
+    pub fn replace_user(&mut self, user: User) -> Result<Option<User>> {
+        let existing = self.config.user(&user.name).cloned();
+        self.replace_entry("users", &user.name, &user, existing)
+    }
+
+    /// Removes the cluster with the given name, along with the comments right
+    /// above it. Returns the cluster that was removed.
+    pub fn remove_cluster(&mut self, name: &str) -> Result<Option<Cluster>> {
+        let existing = self.config.cluster(name).cloned();
+        if existing.is_some() {
+            self.remove_entry("clusters", name)?;
+        }
+        Ok(existing)
+    }
+
+    /// Removes the context with the given name, along with the comments right
+    /// above it. Returns the context that was removed.
+    pub fn remove_context(&mut self, name: &str) -> Result<Option<Context>> {
+        let existing = self.config.context(name).cloned();
+        if existing.is_some() {
+            self.remove_entry("contexts", name)?;
+        }
+        Ok(existing)
+    }
+
+    /// Removes the user with the given name, along with the comments right
+    /// above it. Returns the user that was removed.
+    pub fn remove_user(&mut self, name: &str) -> Result<Option<User>> {
+        let existing = self.config.user(name).cloned();
+        if existing.is_some() {
+            self.remove_entry("users", name)?;
+        }
+        Ok(existing)
+    }
+
+    /// Writes the edits back to the file the document was loaded from, locking
+    /// it like [`Config::save`](../struct.Config.html#method.save) does.
+    ///
+    /// An error is returned if the file was changed by someone else since it
+    /// was loaded, instead of overwriting their changes.
+    pub fn save(&mut self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => bail!("the document was not loaded from a file"),
+        };
+
+        let _lock = FileLock::acquire(&path)?;
+        let current = read_to_string(&path)
+            .chain_err(|| ErrorKind::MissingConfigFile(format!("{}", path.display())))?;
+        if self.loaded.as_ref() != Some(&current) {
+            bail!(
+                "config file '{}' changed since it was loaded",
+                path.display()
+            );
+        }
+
+        let text = self.to_yaml_string();
+        write_atomic(&path, &text)?;
+        self.loaded = Some(text);
+        Ok(())
+    }
+
+    /// Writes the document to the given path, replacing the file if it
+    /// already exists.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let _lock = FileLock::acquire(path.as_ref())?;
+        write_atomic(path.as_ref(), &self.to_yaml_string())
+    }
+
+    fn replace_entry<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        name: &str,
+        entry: &T,
+        existing: Option<T>,
+    ) -> Result<Option<T>> {
+        let existing_value = match &existing {
+            Some(existing) => Some(serde_yaml::to_value(existing)?),
+            None => None,
+        };
+        let new_value = serde_yaml::to_value(entry)?;
+        if existing_value.as_ref() == Some(&new_value) {
+            return Ok(existing);
+        }
+
+        let mut lines = self.lines.clone();
+        match find_item(&lines, key, name)? {
+            Some((_, item, _)) => {
+                let written = written_entry(&lines, key, name)?;
+                let is_flow = lines[item.start].trim_start()[1..]
+                    .trim_start()
+                    .starts_with('{');
+                let replacement = match (&written, &new_value) {
+                    (Some(Value::Mapping(old)), Value::Mapping(new)) if !is_flow => {
+                        // Keys that are left out when the entry is written,
+                        // like `insecure-skip-tls-verify: false`, are kept
+                        let redundant = |path: &[Value]| {
+                            serde_yaml::from_value::<T>(without(&Value::Mapping(old.clone()), path))
+                                .and_then(|entry| serde_yaml::to_value(&entry))
+                                .ok()
+                                == existing_value
+                        };
+                        let block = Block {
+                            start: item.start,
+                            end: item.end,
+                            key_indent: item.key_indent,
+                            dash: Some(item.indent),
+                        };
+                        update_block(&lines, &block, old, new, &[], &redundant)?
+                    }
+                    _ => render_item(entry, item.indent)?,
+                };
+                lines.splice(item.start..item.end, replacement);
+            }
+            None => match find_section(&lines, key) {
+                Some(section) => {
+                    make_block_list(&mut lines, &section, key)?;
+                    let items = items(&lines, &section, key)?;
+                    let at = items.last().map_or(section.key_line + 1, |i| i.end);
+                    let indent = items.first().map_or(0, |i| i.indent);
+                    let rendered = render_item(entry, indent)?;
+                    lines.splice(at..at, rendered);
+                }
+                None => {
+                    let at = end_of_document(&lines);
+                    let mut rendered = vec![format!("{}:", key)];
+                    rendered.extend(render_item(entry, 0)?);
+                    lines.splice(at..at, rendered);
+                }
+            },
+        }
+        self.apply(lines)?;
+        Ok(existing)
+    }
+
+    fn remove_entry(&mut self, key: &str, name: &str) -> Result<()> {
+        let mut lines = self.lines.clone();
+        if let Some((section, item, count)) = find_item(&lines, key, name)? {
+            lines.drain(item.comments_start..item.end);
+            if count == 1 {
+                // Nothing is left in the list, which would make it null
+                lines[section.key_line] = format!("{}: []", key);
+            }
+        }
+        self.apply(lines)
+    }
+
+    /// Takes the edited lines if they still make a valid config
+    fn apply(&mut self, lines: Vec<String>) -> Result<()> {
+        let config =
+            parse_config(&lines.join("\n")).chain_err(|| "the edit did not give a valid config")?;
+        self.lines = lines;
+        self.config = config;
+        Ok(())
+    }
+}
+
+fn parse_config(text: &str) -> Result<Config> {
+    let mut config: Config = serde_yaml::from_str(text)?;
+    config.reindex();
+    Ok(config)
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether the line has something other than whitespace and a comment
+fn is_content(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+/// Splits `key: value` into the key and everything after the colon
+fn split_key(content: &str) -> Option<(&str, &str)> {
+    if content.starts_with('-') || content.starts_with('#') {
+        return None;
+    }
+    let colon = content
+        .char_indices()
+        .find(|&(i, c)| c == ':' && (i + 1 == content.len() || content[i + 1..].starts_with(' ')))
+        .map(|(i, _)| i)?;
+    let key = content[..colon]
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'');
+    Some((key, &content[colon + 1..]))
+}
+
+/// The value after a key, without a trailing comment
+fn strip_comment(rest: &str) -> &str {
+    let rest = rest.trim();
+    if rest.starts_with('"') || rest.starts_with('\'') {
+        return rest;
+    }
+    match rest.find(" #") {
+        Some(i) => rest[..i].trim_end(),
+        None if rest.starts_with('#') => "",
+        None => rest,
+    }
+}
+
+/// Writes `key: value` at the given indentation, keeping a trailing comment
+/// of the line it replaces
+fn with_value(line: &str, indent: usize, key: &str, value: &str) -> String {
+    let rest = split_key(line.trim_start()).map_or("", |(_, rest)| rest);
+    let trimmed = rest.trim_start();
+    let comment = if trimmed.starts_with('"') || trimmed.starts_with('\'') {
+        ""
+    } else {
+        // Keep the spacing before the comment as well
+        rest.find(" #")
+            .map_or("", |i| &rest[rest[..i].trim_end().len()..])
+    };
+    format!("{}{}: {}{}", " ".repeat(indent), key, value, comment)
+}
+
+/// Parses a scalar the way yaml does, i.e. without its quotes
+fn scalar_value(rest: &str) -> Option<String> {
+    match serde_yaml::from_str(strip_comment(rest)) {
+        Ok(Value::String(value)) => Some(value),
+        Ok(Value::Number(value)) => Some(value.to_string()),
+        Ok(Value::Bool(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn render_scalar(value: &str) -> Result<String> {
+    yaml::scalar(&Value::String(value.to_string()))
+}
+
+/// Renders an entry as a list item at the given indentation, the way
+/// [`Config::to_yaml_string`](../struct.Config.html#method.to_yaml_string)
+/// does
+fn render_item<T: Serialize>(entry: &T, indent: usize) -> Result<Vec<String>> {
+    yaml::render(&Value::Sequence(vec![serde_yaml::to_value(entry)?]), indent)
+}
+
+/// The line to add new top level keys at, before the final newline
+fn end_of_document(lines: &[String]) -> usize {
+    match lines.last() {
+        Some(line) if line.is_empty() => lines.len() - 1,
+        _ => lines.len(),
+    }
+}
+
+fn find_section(lines: &[String], key: &str) -> Option<Section> {
+    let key_line = lines.iter().position(|line| {
+        indent(line) == 0 && is_content(line) && split_key(line).map(|(k, _)| k) == Some(key)
+    })?;
+
+    let mut end = key_line + 1;
+    for (i, line) in lines.iter().enumerate().skip(key_line + 1) {
+        if !is_content(line) {
+            continue;
+        }
+        if indent(line) == 0 && !line.starts_with('-') {
+            break;
+        }
+        end = i + 1;
+    }
+    Some(Section { key_line, end })
+}
+
+/// Turns `key: []` or an empty `key:` into a block list that entries can be
+/// added to
+fn make_block_list(lines: &mut [String], section: &Section, key: &str) -> Result<()> {
+    let rest = split_key(&lines[section.key_line]).map_or("", |(_, rest)| rest);
+    match strip_comment(rest) {
+        "" => Ok(()),
+        "[]" | "null" | "~" => {
+            lines[section.key_line] = format!("{}:", key);
+            Ok(())
+        }
+        _ => bail!("'{}' can not be edited in place", key),
+    }
+}
+
+fn items(lines: &[String], section: &Section, key: &str) -> Result<Vec<Item>> {
+    let mut starts = Vec::new();
+    let mut item_indent = None;
+    for (i, line) in lines
+        .iter()
+        .enumerate()
+        .take(section.end)
+        .skip(section.key_line + 1)
+    {
+        if !is_content(line) {
+            continue;
+        }
+        let content = line.trim_start();
+        let is_item = content == "-" || content.starts_with("- ");
+        match item_indent {
+            None if is_item => {
+                item_indent = Some(indent(line));
+                starts.push(i);
+            }
+            None => bail!("'{}' can not be edited in place", key),
+            Some(item_indent) if is_item && indent(line) == item_indent => starts.push(i),
+            Some(_) => {}
+        }
+    }
+
+    let mut items: Vec<Item> = Vec::new();
+    for (n, &start) in starts.iter().enumerate() {
+        let next = starts.get(n + 1).copied().unwrap_or(section.end);
+        let end = (start..next)
+            .rev()
+            .find(|&i| is_content(&lines[i]))
+            .map_or(start + 1, |i| i + 1);
+
+        let previous_end = items.last().map_or(section.key_line + 1, |i| i.end);
+        let mut comments_start = start;
+        while comments_start > previous_end
+            && lines[comments_start - 1].trim_start().starts_with('#')
+        {
+            comments_start -= 1;
+        }
+
+        let line = &lines[start];
+        let indent = indent(line);
+        let after_dash = &line.trim_start()[1..];
+        let key_indent = if after_dash.trim().is_empty() {
+            (start + 1..end)
+                .find(|&i| is_content(&lines[i]))
+                .map_or(indent + 2, |i| self::indent(&lines[i]))
+        } else {
+            indent + 1 + after_dash.len() - after_dash.trim_start().len()
+        };
+
+        items.push(Item {
+            comments_start,
+            start,
+            end,
+            indent,
+            key_indent,
+        });
+    }
+    Ok(items)
+}
+
+/// Finds a key of an entry, returning its line and everything after the colon
+fn item_key(lines: &[String], item: &Item, key: &str) -> Option<(usize, String)> {
+    (item.start..item.end).find_map(|i| {
+        let line = &lines[i];
+        let content = if i == item.start {
+            line.trim_start()[1..].trim_start()
+        } else if indent(line) == item.key_indent {
+            line.trim_start()
+        } else {
+            return None;
+        };
+        match split_key(content) {
+            Some((k, rest)) if k == key => Some((i, rest.to_string())),
+            _ => None,
+        }
+    })
+}
+
+/// Finds the first entry with the given name in the list under `key`,
+/// returning the list, the entry and how many entries the list has
+fn find_item(lines: &[String], key: &str, name: &str) -> Result<Option<(Section, Item, usize)>> {
+    let section = match find_section(lines, key) {
+        Some(section) => section,
+        None => return Ok(None),
+    };
+    let items = items(lines, &section, key)?;
+    let count = items.len();
+    let item = items.into_iter().find(|item| {
+        item_key(lines, item, "name").and_then(|(_, rest)| scalar_value(&rest))
+            == Some(name.to_string())
+    });
+    Ok(item.map(|item| (section, item, count)))
+}
+
+/// The first entry with the given name in the list under `key`, as it is
+/// written in the file
+fn written_entry(lines: &[String], key: &str, name: &str) -> Result<Option<Value>> {
+    let document: Value = serde_yaml::from_str(&lines.join("\n"))?;
+    Ok(document
+        .get(key)
+        .and_then(Value::as_sequence)
+        .and_then(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.get("name").and_then(Value::as_str) == Some(name))
+        })
+        .cloned())
+}
+
+/// The value with the key at the given path removed
+fn without(value: &Value, path: &[Value]) -> Value {
+    match (value, path) {
+        (Value::Mapping(map), [key]) => {
+            let mut map = map.clone();
+            map.remove(key);
+            Value::Mapping(map)
+        }
+        (Value::Mapping(map), [key, rest @ ..]) => {
+            let mut map = map.clone();
+            if let Some(inner) = map.get(key).map(|inner| without(inner, rest)) {
+                map.insert(key.clone(), inner);
+            }
+            Value::Mapping(map)
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Whether two values are the same, regardless of the order of their keys
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, value)| b.get(key).is_some_and(|other| same(value, other)))
+        }
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+fn block_keys(lines: &[String], block: &Block) -> Vec<Key> {
+    let mut keys: Vec<Key> = Vec::new();
+    for (i, line) in lines.iter().enumerate().take(block.end).skip(block.start) {
+        if !is_content(line) {
+            continue;
+        }
+        let content = if block.dash.is_some() && i == block.start {
+            line.trim_start()[1..].trim_start()
+        } else if indent(line) == block.key_indent {
+            line.trim_start()
+        } else {
+            ""
+        };
+        match split_key(content) {
+            Some((name, rest)) => keys.push(Key {
+                name: name.to_string(),
+                line: i,
+                end: i + 1,
+                prefix: line[..line.len() - content.len()].to_string(),
+                rest: rest.to_string(),
+            }),
+            // Deeper lines, and the items of a list written at the
+            // indentation of its key, belong to the value of the last key
+            None => {
+                if let Some(key) = keys.last_mut() {
+                    key.end = i + 1;
+                }
+            }
+        }
+    }
+    keys
+}
+
+/// Writes the lines of a block mapping again, changing only the keys whose
+/// values differ between `old` and `new`. New keys are added before the first
+/// key that sorts after them, like `set_namespace` does.
+fn update_block(
+    lines: &[String],
+    block: &Block,
+    old: &Mapping,
+    new: &Mapping,
+    path: &[Value],
+    redundant: &dyn Fn(&[Value]) -> bool,
+) -> Result<Vec<String>> {
+    let key_prefix = " ".repeat(block.key_indent);
+    let mut added: Vec<(&Value, &Value)> = new
+        .iter()
+        .filter(|(key, _)| !old.contains_key(key))
+        .collect();
+    let mut result = Vec::new();
+    let mut position = block.start;
+    let mut dash_removed = false;
+
+    for key in block_keys(lines, block) {
+        let on_dash = block.dash.is_some() && key.line == block.start;
+        result.extend_from_slice(&lines[position..key.line]);
+        position = key.end;
+
+        if !on_dash {
+            while let Some(i) = added
+                .iter()
+                .position(|(name, _)| name.as_str() < Some(key.name.as_str()))
+            {
+                let (name, value) = added.remove(i);
+                result.extend(render_key(&key_prefix, &key_prefix, name, value)?);
+            }
+        }
+
+        let name = Value::String(key.name.clone());
+        let mut key_path = path.to_vec();
+        key_path.push(name.clone());
+        match (old.get(&name), new.get(&name)) {
+            (Some(old), Some(new)) if same(old, new) => {
+                result.extend_from_slice(&lines[key.line..key.end]);
+            }
+            (_, None) if redundant(&key_path) => {
+                result.extend_from_slice(&lines[key.line..key.end]);
+            }
+            (_, None) => dash_removed |= on_dash,
+            (Some(Value::Mapping(old)), Some(Value::Mapping(new)))
+                if strip_comment(&key.rest).is_empty()
+                    && key.end > key.line + 1
+                    && !new.is_empty() =>
+            {
+                let child = (key.line + 1..key.end)
+                    .find(|&i| is_content(&lines[i]))
+                    .map_or(block.key_indent + 2, |i| indent(&lines[i]));
+                let inner = Block {
+                    start: key.line + 1,
+                    end: key.end,
+                    key_indent: child,
+                    dash: None,
+                };
+                result.push(lines[key.line].clone());
+                result.extend(update_block(lines, &inner, old, new, &key_path, redundant)?);
+            }
+            // Values that fit on the line of the key replace its old value,
+            // like `{}` for a mapping that has no keys left
+            (_, Some(new)) => match single_line(new) {
+                Some(value) => {
+                    let content = &lines[key.line][key.prefix.len()..];
+                    result.push(format!(
+                        "{}{}",
+                        key.prefix,
+                        with_value(content, 0, &key.name, &value)
+                    ));
+                }
+                None => result.extend(render_key(&key.prefix, &key_prefix, &name, new)?),
+            },
+        }
+    }
+    for (name, value) in added {
+        result.extend(render_key(&key_prefix, &key_prefix, name, value)?);
+    }
+    result.extend_from_slice(&lines[position..block.end]);
+
+    // The `-` of the entry moves to the key that is now first
+    if let (true, Some(dash)) = (dash_removed, block.dash) {
+        if let Some(line) = result.iter_mut().find(|line| is_content(line)) {
+            *line = format!("{}-{}", " ".repeat(dash), &line[dash + 1..]);
+        }
+    }
+    Ok(result)
+}
+
+/// Renders a value that fits on the line of its key, i.e. a scalar or an
+/// empty mapping or list
+fn single_line(value: &Value) -> Option<String> {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => None,
+        Value::Sequence(items) if !items.is_empty() => None,
+        _ => yaml::scalar(value).ok(),
+    }
+}
+
+/// Renders `key: value`, starting the first line with `prefix` and indenting
+/// the other lines by `indent`
+fn render_key(prefix: &str, indent: &str, key: &Value, value: &Value) -> Result<Vec<String>> {
+    let mut map = Mapping::new();
+    map.insert(key.clone(), value.clone());
+    Ok(yaml::render(&Value::Mapping(map), 0)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { prefix } else { indent };
+            format!("{}{}", prefix, line)
+        })
+        .collect())
+}
@@ -0,0 +1,425 @@
+//! Changing the clusters, contexts and users of a config, the way the
+//! `kubectl config` subcommands do
+
+use crate::auth_provider::AuthProvider;
+use crate::errors::*;
+use crate::exec::ExecConfig;
+use crate::index::position;
+use crate::{Cluster, Config, Context, User};
+use std::collections::HashMap;
+use std::mem;
+use std::path::PathBuf;
+
+/// An entry taken out of a config by one of the `remove_*` methods of
+/// `Config`, along with what still refers to it
+#[derive(Debug, Clone)]
+pub struct Removed<T> {
+    /// The entry that was removed
+    pub entry: T,
+
+    /// The names of the contexts that still refer to the removed cluster or
+    /// user
+    pub referenced_by: Vec<String>,
+
+    /// Whether the removed context was the current context. Like `kubectl`,
+    /// `current-context` is left pointing to it.
+    pub was_current: bool,
+}
+
+impl<T> Removed<T> {
+    /// Describes the references left dangling by the removal, for showing to
+    /// the user
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self
+            .referenced_by
+            .iter()
+            .map(|context| format!("context '{}' still refers to the removed entry", context))
+            .collect();
+        if self.was_current {
+            warnings.push(
+                "this removed your active context, use Config::use_context to select a \
+                 different one"
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+}
+
+/// The fields of a cluster to change with
+/// [`Config::upsert_cluster`](../struct.Config.html#method.upsert_cluster),
+/// like the flags of `kubectl config set-cluster`. Fields that are `None` are
+/// left as they are, and empty strings unset the field.
+#[derive(Debug, Clone, Default)]
+pub struct ClusterPatch {
+    /// The address of the server
+    pub server: Option<String>,
+
+    /// The certificate authority file, which clears
+    /// `certificate-authority-data` and `insecure-skip-tls-verify`
+    pub certificate_authority: Option<PathBuf>,
+
+    /// The embedded certificate authority, which clears
+    /// `certificate-authority` and `insecure-skip-tls-verify`
+    pub certificate_authority_data: Option<String>,
+
+    /// Whether to skip verifying the server. Setting it to true clears the
+    /// certificate authority.
+    pub insecure_skip_tls_verify: Option<bool>,
+
+    /// The proxy to use for all requests to the cluster
+    pub proxy_url: Option<String>,
+
+    /// The name to verify the certificate of the server against
+    pub tls_server_name: Option<String>,
+
+    /// Whether responses from the server should not be compressed
+    pub disable_compression: Option<bool>,
+}
+
+/// The fields of a context to change with
+/// [`Config::upsert_context`](../struct.Config.html#method.upsert_context),
+/// like the flags of `kubectl config set-context`. Fields that are `None` are
+/// left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct ContextPatch {
+    /// The name of the cluster
+    pub cluster: Option<String>,
+
+    /// The name of the user
+    pub user: Option<String>,
+
+    /// The default namespace, which is unset by an empty string
+    pub namespace: Option<String>,
+}
+
+/// The fields of a user to change with
+/// [`Config::upsert_user`](../struct.Config.html#method.upsert_user), like
+/// the flags of `kubectl config set-credentials`. Fields that are `None` are
+/// left as they are, and empty strings unset the field.
+///
+/// Like `kubectl`, setting a token clears the basic auth credentials and the
+/// other way around.
+#[derive(Debug, Clone, Default)]
+pub struct UserPatch {
+    /// The client certificate file, which clears `client-certificate-data`
+    pub client_certificate: Option<PathBuf>,
+
+    /// The embedded client certificate, which clears `client-certificate`
+    pub client_certificate_data: Option<String>,
+
+    /// The client key file, which clears `client-key-data`
+    pub client_key: Option<PathBuf>,
+
+    /// The embedded client key, which clears `client-key`
+    pub client_key_data: Option<String>,
+
+    /// The bearer token
+    pub token: Option<String>,
+
+    /// The username for basic auth
+    pub username: Option<String>,
+
+    /// The password for basic auth
+    pub password: Option<String>,
+
+    /// The exec credential plugin, replacing the one the user has
+    pub exec: Option<ExecConfig>,
+
+    /// The auth-provider, replacing the one the user has
+    pub auth_provider: Option<AuthProvider>,
+}
+
+impl ClusterPatch {
+    fn apply(self, cluster: &mut Cluster) {
+        if let Some(server) = self.server {
+            cluster.server = server;
+        }
+        if let Some(insecure_skip_tls_verify) = self.insecure_skip_tls_verify {
+            cluster.insecure_skip_tls_verify = insecure_skip_tls_verify;
+            if insecure_skip_tls_verify {
+                cluster.certificate_authority = None;
+                cluster.certificate_authority_data = None;
+            }
+        }
+        if let Some(certificate_authority) = self.certificate_authority {
+            cluster.certificate_authority = non_empty_path(certificate_authority);
+            if cluster.certificate_authority.is_some() {
+                cluster.certificate_authority_data = None;
+                cluster.insecure_skip_tls_verify = false;
+            }
+        }
+        if let Some(certificate_authority_data) = self.certificate_authority_data {
+            cluster.certificate_authority_data = non_empty(certificate_authority_data);
+            if cluster.certificate_authority_data.is_some() {
+                cluster.certificate_authority = None;
+                cluster.insecure_skip_tls_verify = false;
+            }
+        }
+        if let Some(proxy_url) = self.proxy_url {
+            cluster.proxy_url = non_empty(proxy_url);
+        }
+        if let Some(tls_server_name) = self.tls_server_name {
+            cluster.tls_server_name = non_empty(tls_server_name);
+        }
+        if let Some(disable_compression) = self.disable_compression {
+            cluster.disable_compression = disable_compression;
+        }
+    }
+}
+
+impl ContextPatch {
+    fn apply(self, context: &mut Context) {
+        if let Some(cluster) = self.cluster {
+            context.cluster = cluster;
+        }
+        if let Some(user) = self.user {
+            context.user = user;
+        }
+        if let Some(namespace) = self.namespace {
+            context.namespace = non_empty(namespace);
+        }
+    }
+}
+
+impl UserPatch {
+    fn apply(self, user: &mut User) {
+        if let Some(client_certificate) = self.client_certificate {
+            user.client_certificate = non_empty_path(client_certificate);
+            user.client_certificate_data = None;
+        }
+        if let Some(client_certificate_data) = self.client_certificate_data {
+            user.client_certificate_data = non_empty(client_certificate_data);
+            user.client_certificate = None;
+        }
+        if let Some(client_key) = self.client_key {
+            user.client_key = non_empty_path(client_key);
+            user.client_key_data = None;
+        }
+        if let Some(client_key_data) = self.client_key_data {
+            user.client_key_data = non_empty(client_key_data);
+            user.client_key = None;
+        }
+
+        let set_token = self.token.as_ref().is_some_and(|t| !t.is_empty());
+        let set_basic = self.username.as_ref().is_some_and(|u| !u.is_empty())
+            || self.password.as_ref().is_some_and(|p| !p.is_empty());
+        if let Some(token) = self.token {
+            user.token = non_empty(token);
+        }
+        if let Some(username) = self.username {
+            user.username = non_empty(username);
+        }
+        if let Some(password) = self.password {
+            user.password = non_empty(password);
+        }
+        // Only one of the two ways to authenticate is kept
+        if set_token && !set_basic {
+            user.username = None;
+            user.password = None;
+        } else if set_basic && !set_token {
+            user.token = None;
+        }
+
+        if let Some(exec) = self.exec {
+            user.exec = Some(exec);
+        }
+        if let Some(auth_provider) = self.auth_provider {
+            user.auth_provider = Some(auth_provider);
+        }
+    }
+}
+
+impl Config {
+    /// Changes the fields of the cluster with the given name that are set in
+    /// the patch, like `kubectl config set-cluster`, adding the cluster when
+    /// there is none. Returns the cluster as it was before.
+    pub fn upsert_cluster(&mut self, name: &str, patch: ClusterPatch) -> Option<Cluster> {
+        let existing = self.cluster(name).cloned();
+        let mut cluster = existing.clone().unwrap_or_else(|| Cluster {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        patch.apply(&mut cluster);
+        self.replace_cluster(cluster);
+        existing
+    }
+
+    /// Changes the fields of the context with the given name that are set in
+    /// the patch, like `kubectl config set-context`, adding the context when
+    /// there is none. Returns the context as it was before.
+    pub fn upsert_context(&mut self, name: &str, patch: ContextPatch) -> Option<Context> {
+        let existing = self.context(name).cloned();
+        let mut context = existing.clone().unwrap_or_else(|| Context {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        patch.apply(&mut context);
+        self.replace_context(context);
+        existing
+    }
+
+    /// Changes the fields of the user with the given name that are set in the
+    /// patch, like `kubectl config set-credentials`, adding the user when
+    /// there is none. Returns the user as it was before.
+    pub fn upsert_user(&mut self, name: &str, patch: UserPatch) -> Option<User> {
+        let existing = self.user(name).cloned();
+        let mut user = existing.clone().unwrap_or_else(|| User {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        patch.apply(&mut user);
+        self.replace_user(user);
+        existing
+    }
+
+    /// Adds the cluster, or replaces the cluster with the same name in place.
+    /// Returns the cluster that was replaced.
+    ///
+    /// The whole cluster is replaced, use
+    /// [`upsert_cluster`](#method.upsert_cluster) to only change some of its
+    /// fields.
+    pub fn replace_cluster(&mut self, cluster: Cluster) -> Option<Cluster> {
+        replace_entry(&mut self.clusters, &mut self.index.clusters, cluster, |c| {
+            &c.name
+        })
+    }
+
+    /// Adds the context, or replaces the context with the same name in place.
+    /// Returns the context that was replaced. The whole context is replaced,
+    /// use [`upsert_context`](#method.upsert_context) to only change some of
+    /// its fields.
+    pub fn replace_context(&mut self, context: Context) -> Option<Context> {
+        replace_entry(&mut self.contexts, &mut self.index.contexts, context, |c| {
+            &c.name
+        })
+    }
+
+    /// Adds the user, or replaces the user with the same name in place.
+    /// Returns the user that was replaced. The whole user is replaced, use
+    /// [`upsert_user`](#method.upsert_user) to only change some of its
+    /// fields.
+    pub fn replace_user(&mut self, user: User) -> Option<User> {
+        replace_entry(&mut self.users, &mut self.index.users, user, |u| &u.name)
+    }
+
+    /// Removes the cluster with the given name. Contexts referring to it are
+    /// left as they are and listed in the result.
+    pub fn remove_cluster(&mut self, name: &str) -> Option<Removed<Cluster>> {
+        let position = position(&self.clusters, &self.index.clusters, name, |c| &c.name)?;
+        let entry = self.clusters.remove(position);
+        self.reindex();
+
+        Some(Removed {
+            referenced_by: self.contexts_where(|c| c.cluster == name),
+            entry,
+            was_current: false,
+        })
+    }
+
+    /// Removes the context with the given name. Like `kubectl`,
+    /// `current-context` is left as it is when it names the removed context.
+    pub fn remove_context(&mut self, name: &str) -> Option<Removed<Context>> {
+        let position = position(&self.contexts, &self.index.contexts, name, |c| &c.name)?;
+        let entry = self.contexts.remove(position);
+        self.reindex();
+
+        Some(Removed {
+            entry,
+            referenced_by: Vec::new(),
+            was_current: self.current_context.as_deref() == Some(name),
+        })
+    }
+
+    /// Removes the user with the given name. Contexts referring to it are left
+    /// as they are and listed in the result.
+    pub fn remove_user(&mut self, name: &str) -> Option<Removed<User>> {
+        let position = position(&self.users, &self.index.users, name, |u| &u.name)?;
+        let entry = self.users.remove(position);
+        self.reindex();
+
+        Some(Removed {
+            referenced_by: self.contexts_where(|c| c.user == name),
+            entry,
+            was_current: false,
+        })
+    }
+
+    /// Renames a context, updating `current-context` when it names the
+    /// context.
+    ///
+    /// Returns an error when there is no context named `from`, or when there
+    /// already is one named `to`.
+    pub fn rename_context(&mut self, from: &str, to: &str) -> Result<()> {
+        let position = position(&self.contexts, &self.index.contexts, from, |c| &c.name)
+            .ok_or_else(|| ErrorKind::UnknownContext(from.to_string()))?;
+        if from == to {
+            return Ok(());
+        }
+        if self.context(to).is_some() {
+            bail!(ErrorKind::ContextExists(to.to_string()));
+        }
+
+        self.contexts[position].name = to.to_string();
+        self.index.contexts.remove(from);
+        self.index.contexts.insert(to.to_string(), position);
+        self.origins.rename_context(from, to);
+        if self.current_context.as_deref() == Some(from) {
+            self.current_context = Some(to.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Sets `current-context` to the given context.
+    ///
+    /// Returns an error when there is no context with the name.
+    pub fn use_context(&mut self, name: &str) -> Result<()> {
+        if self.context(name).is_none() {
+            bail!(ErrorKind::UnknownContext(name.to_string()));
+        }
+        self.current_context = Some(name.to_string());
+        Ok(())
+    }
+
+    fn contexts_where<F>(&self, f: F) -> Vec<String>
+    where
+        F: Fn(&Context) -> bool,
+    {
+        self.contexts
+            .iter()
+            .filter(|c| f(c))
+            .map(|c| c.name.clone())
+            .collect()
+    }
+}
+
+/// Replaces the entry with the same name in place, or adds it to the end,
+/// keeping the index up to date
+fn replace_entry<T, F>(
+    entries: &mut Vec<T>,
+    index: &mut HashMap<String, usize>,
+    entry: T,
+    name_of: F,
+) -> Option<T>
+where
+    F: Fn(&T) -> &String,
+{
+    let name = name_of(&entry).clone();
+    match position(entries, index, &name, &name_of) {
+        Some(position) => Some(mem::replace(&mut entries[position], entry)),
+        None => {
+            index.insert(name, entries.len());
+            entries.push(entry);
+            None
+        }
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+fn non_empty_path(path: PathBuf) -> Option<PathBuf> {
+    Some(path).filter(|path| !path.as_os_str().is_empty())
+}
@@ -0,0 +1,537 @@
+//! The module holding the `ExecConfig` struct used for exec credential plugins
+
+use crate::cluster::Cluster;
+use crate::errors::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::DateTime;
+use serde_yaml::Mapping;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{create_dir_all, read_to_string, OpenOptions};
+use std::io::{stdin, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The api version used when the exec config does not specify one
+const DEFAULT_API_VERSION: &str = "client.authentication.k8s.io/v1";
+
+/// The cluster extension holding configuration for exec plugins, passed to
+/// them as `config` in the cluster info
+const EXEC_EXTENSION: &str = "client.authentication.k8s.io/exec";
+
+/// The variables of the process environment that change which credentials
+/// the common plugins return, like the cloud provider profile or account.
+/// Names ending with `_` match every variable with that prefix.
+const CREDENTIAL_ENV: &[&str] = &[
+    "AWS_",
+    "AZURE_",
+    "CLOUDSDK_",
+    "GOOGLE_",
+    "HOME",
+    "KUBECONFIG",
+    "USER",
+];
+
+/// The `exec` stanza of a user, describing an external command that provides
+/// the credentials for the user. This is what EKS, GKE and most OIDC setups
+/// use to log in.
+///
+/// See the [kubernetes documentation](https://kubernetes.io/docs/reference/access-authn-authz/authentication/#client-go-credential-plugins)
+/// for the details of the protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecConfig {
+    /// The `ExecCredential` api version the plugin speaks, i.e.
+    /// "client.authentication.k8s.io/v1"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+
+    /// The command to execute
+    pub command: String,
+
+    /// Arguments to pass to the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+
+    /// Extra environment variables to set for the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<ExecEnvVar>>,
+
+    /// A message shown to the user when the command can not be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_hint: Option<String>,
+
+    /// When true the cluster information is passed to the plugin in the
+    /// `KUBERNETES_EXEC_INFO` environment variable
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub provide_cluster_info: bool,
+
+    /// Whether the plugin needs the standard input of the process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive_mode: Option<InteractiveMode>,
+
+    /// Any keys in the `exec` mapping that are not known to this crate.
+    /// These are kept so that no data is lost when the config is written back.
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// An environment variable set when running an exec plugin
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ExecEnvVar {
+    /// The name of the environment variable
+    pub name: String,
+
+    /// The value of the environment variable
+    pub value: String,
+}
+
+/// Describes the plugin's relationship with the standard input of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum InteractiveMode {
+    /// The plugin never reads from standard input
+    Never,
+
+    /// The plugin reads from standard input if it is available
+    IfAvailable,
+
+    /// The plugin requires standard input, running it fails without it
+    Always,
+}
+
+/// The `ExecCredential` object printed by an exec plugin
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredential {
+    /// The api version of the credential, i.e. "client.authentication.k8s.io/v1"
+    pub api_version: Option<String>,
+
+    /// Will always be "ExecCredential"
+    pub kind: Option<String>,
+
+    /// The credentials returned by the plugin
+    pub status: Option<ExecCredentialStatus>,
+}
+
+/// The credentials returned by an exec plugin. Either `token` or both
+/// `client_certificate_data` and `client_key_data` are set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredentialStatus {
+    /// A bearer token used to authenticate with the cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// A PEM encoded client certificate used to authenticate with the cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_certificate_data: Option<String>,
+
+    /// The PEM encoded private key of `client_certificate_data`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key_data: Option<String>,
+
+    /// An RFC 3339 timestamp of when the credentials expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_timestamp: Option<String>,
+}
+
+impl ExecConfig {
+    /// Runs the plugin and returns the credentials it printed.
+    pub fn run(&self) -> Result<ExecCredential> {
+        self.run_with_cluster(None)
+    }
+
+    /// Runs the plugin and returns the credentials it printed. When
+    /// `provide_cluster_info` is set the given cluster is passed along to the
+    /// plugin.
+    pub fn run_with_cluster(&self, cluster: Option<&Cluster>) -> Result<ExecCredential> {
+        let api_version = self.api_version.as_deref().unwrap_or(DEFAULT_API_VERSION);
+        let interactive = match self
+            .interactive_mode
+            .unwrap_or(InteractiveMode::IfAvailable)
+        {
+            InteractiveMode::Never => false,
+            InteractiveMode::IfAvailable => stdin().is_terminal(),
+            InteractiveMode::Always => {
+                if !stdin().is_terminal() {
+                    bail!(ErrorKind::ExecPlugin(
+                        self.command.clone(),
+                        "plugin requires an interactive terminal".to_string()
+                    ));
+                }
+                true
+            }
+        };
+
+        let mut spec = serde_json::json!({ "interactive": interactive });
+        if let (true, Some(cluster)) = (self.provide_cluster_info, cluster) {
+            spec["cluster"] = cluster_info(cluster)?;
+        }
+        let exec_info = serde_json::json!({
+            "apiVersion": api_version,
+            "kind": "ExecCredential",
+            "spec": spec,
+        });
+
+        let mut command = Command::new(&self.command);
+        command
+            .args(self.args.iter().flatten())
+            .envs(self.env.iter().flatten().map(|e| (&e.name, &e.value)))
+            .env("KUBERNETES_EXEC_INFO", exec_info.to_string())
+            .stdin(if interactive {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .stderr(Stdio::inherit());
+
+        let output = command.output().chain_err(|| {
+            let hint = self
+                .install_hint
+                .as_deref()
+                .unwrap_or("command could not be run");
+            ErrorKind::ExecPlugin(self.command.clone(), hint.to_string())
+        })?;
+
+        if !output.status.success() {
+            bail!(ErrorKind::ExecPlugin(
+                self.command.clone(),
+                format!("plugin exited with {}", output.status)
+            ));
+        }
+
+        let credential: ExecCredential =
+            serde_json::from_slice(&output.stdout).chain_err(|| {
+                ErrorKind::ExecPlugin(self.command.clone(), "invalid output".to_string())
+            })?;
+
+        if credential.api_version.as_deref() != Some(api_version) {
+            bail!(ErrorKind::ExecPlugin(
+                self.command.clone(),
+                format!(
+                    "plugin returned api version {:?}, expected '{}'",
+                    credential.api_version, api_version
+                )
+            ));
+        }
+
+        if let Some(timestamp) = credential
+            .status
+            .as_ref()
+            .and_then(|s| s.expiration_timestamp.as_ref())
+        {
+            if DateTime::parse_from_rfc3339(timestamp).is_err() {
+                bail!(ErrorKind::ExecPlugin(
+                    self.command.clone(),
+                    format!(
+                        "plugin returned invalid expirationTimestamp '{}'",
+                        timestamp
+                    )
+                ));
+            }
+        }
+
+        match &credential.status {
+            Some(ExecCredentialStatus { token: Some(_), .. })
+            | Some(ExecCredentialStatus {
+                client_certificate_data: Some(_),
+                client_key_data: Some(_),
+                ..
+            }) => Ok(credential),
+            _ => bail!(ErrorKind::ExecPlugin(
+                self.command.clone(),
+                "plugin returned neither a token nor a client certificate and key".to_string()
+            )),
+        }
+    }
+}
+
+impl ExecCredential {
+    /// The bearer token returned by the plugin, if any
+    pub fn token(&self) -> Option<&str> {
+        self.status.as_ref()?.token.as_deref()
+    }
+
+    /// The PEM encoded client certificate returned by the plugin, if any
+    pub fn client_certificate_data(&self) -> Option<&str> {
+        self.status.as_ref()?.client_certificate_data.as_deref()
+    }
+
+    /// The PEM encoded client key returned by the plugin, if any
+    pub fn client_key_data(&self) -> Option<&str> {
+        self.status.as_ref()?.client_key_data.as_deref()
+    }
+
+    /// The point in time the credentials expire, if the plugin returned one
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        let timestamp = self.status.as_ref()?.expiration_timestamp.as_ref()?;
+        DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(SystemTime::from)
+    }
+
+    /// Whether the credentials are still valid for at least `margin`.
+    /// Credentials without an expiration are always considered valid.
+    pub fn is_valid_for(&self, margin: Duration) -> bool {
+        match self.expires_at() {
+            Some(expires_at) => SystemTime::now() + margin < expires_at,
+            None => true,
+        }
+    }
+}
+
+/// Caches the credentials returned by exec plugins so the plugin is only run
+/// again when the credentials are about to expire.
+///
+/// Credentials are kept in memory, and optionally in a directory on disk so
+/// they can be shared between processes. Credentials without an
+/// `expirationTimestamp` are only cached in memory, and are reused for the
+/// lifetime of the cache.
+///
+/// ```
+/// use kube_conf::exec::ExecCredentialCache;
+/// use std::time::Duration;
+///
+/// let cache = ExecCredentialCache::new()
+///     .with_directory("/tmp/kube-conf-cache")
+///     .with_refresh_before(Duration::from_secs(60));
+/// ```
+#[derive(Debug)]
+pub struct ExecCredentialCache {
+    credentials: Mutex<HashMap<CacheKey, Arc<Mutex<Option<ExecCredential>>>>>,
+    directory: Option<PathBuf>,
+    refresh_before: Duration,
+    env_vars: Vec<String>,
+}
+
+/// Credentials depend on the cluster when the cluster info is passed along,
+/// and on the environment the plugin inherits from this process
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+struct CacheKey {
+    exec: ExecConfig,
+    server: Option<String>,
+    environment: BTreeMap<String, String>,
+}
+
+impl Default for ExecCredentialCache {
+    fn default() -> Self {
+        ExecCredentialCache {
+            credentials: Mutex::new(HashMap::new()),
+            directory: None,
+            refresh_before: Duration::from_secs(30),
+            env_vars: Vec::new(),
+        }
+    }
+}
+
+impl ExecCredentialCache {
+    /// Creates an in-memory cache that refreshes credentials 30 seconds before
+    /// they expire.
+    pub fn new() -> Self {
+        ExecCredentialCache::default()
+    }
+
+    /// Also stores the credentials in the given directory, so that other
+    /// processes using the same directory can reuse them. The directory is
+    /// created when needed.
+    pub fn with_directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Sets how long before their expiration credentials are refreshed.
+    pub fn with_refresh_before(mut self, refresh_before: Duration) -> Self {
+        self.refresh_before = refresh_before;
+        self
+    }
+
+    /// Also keeps the credentials apart by the value of the given variable of
+    /// the process environment. Variables of the common cloud providers, like
+    /// `AWS_PROFILE`, are always taken into account.
+    pub fn with_env_var<S: Into<String>>(mut self, name: S) -> Self {
+        self.env_vars.push(name.into());
+        self
+    }
+
+    /// Gets the credentials for the exec config, running the plugin if there
+    /// are no cached credentials or they are about to expire.
+    pub fn get(&self, exec: &ExecConfig) -> Result<ExecCredential> {
+        self.get_with_cluster(exec, None)
+    }
+
+    /// Like [`get`](#method.get), passing the cluster along to the plugin when
+    /// it asks for cluster info.
+    pub fn get_with_cluster(
+        &self,
+        exec: &ExecConfig,
+        cluster: Option<&Cluster>,
+    ) -> Result<ExecCredential> {
+        let key = self.key(exec, cluster);
+
+        // Only the entry is locked while the plugin runs, so concurrent
+        // callers wait for the same credentials instead of all running the
+        // plugin, without holding up callers of other plugins
+        let entry = self
+            .credentials
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let mut cached = entry
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(credential) = cached.as_ref() {
+            if credential.is_valid_for(self.refresh_before) {
+                return Ok(credential.clone());
+            }
+        }
+
+        if let Some(credential) = self.read_from_disk(&key) {
+            *cached = Some(credential.clone());
+            return Ok(credential);
+        }
+
+        let credential = exec.run_with_cluster(cluster)?;
+        if credential.expires_at().is_some() {
+            // The disk cache is best effort, failing to write it only means
+            // the plugin is run again by the next process
+            let _ = self.write_to_disk(&key, &credential);
+        }
+        *cached = Some(credential.clone());
+
+        Ok(credential)
+    }
+
+    /// Drops any cached credentials for the exec config, i.e. after the
+    /// cluster rejected them.
+    pub fn invalidate(&self, exec: &ExecConfig, cluster: Option<&Cluster>) {
+        let key = self.key(exec, cluster);
+        let entry = self
+            .credentials
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&key)
+            .cloned();
+        if let Some(entry) = entry {
+            *entry
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        }
+
+        if let Some(path) = self.disk_path(&key) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    fn key(&self, exec: &ExecConfig, cluster: Option<&Cluster>) -> CacheKey {
+        let environment = std::env::vars()
+            .filter(|(name, _)| {
+                CREDENTIAL_ENV
+                    .iter()
+                    .copied()
+                    .chain(self.env_vars.iter().map(String::as_str))
+                    .any(|n| match n.strip_suffix('_') {
+                        Some(_) => name.starts_with(n),
+                        None => name == n,
+                    })
+            })
+            .collect();
+
+        CacheKey {
+            exec: exec.clone(),
+            server: cluster
+                .filter(|_| exec.provide_cluster_info)
+                .map(|c| c.server.clone()),
+            environment,
+        }
+    }
+
+    fn disk_path(&self, key: &CacheKey) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        let key = serde_json::to_string(key).ok()?;
+        Some(directory.join(format!("{:016x}.json", fnv1a(key.as_bytes()))))
+    }
+
+    fn read_from_disk(&self, key: &CacheKey) -> Option<ExecCredential> {
+        let conf = read_to_string(self.disk_path(key)?).ok()?;
+        let credential: ExecCredential = serde_json::from_str(&conf).ok()?;
+
+        // Only credentials with a known expiration are written to disk
+        credential.expires_at()?;
+        if credential.is_valid_for(self.refresh_before) {
+            Some(credential)
+        } else {
+            None
+        }
+    }
+
+    fn write_to_disk(&self, key: &CacheKey, credential: &ExecCredential) -> Result<()> {
+        let path = match self.disk_path(key) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(directory) = &self.directory {
+            create_dir_all(directory)?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string(credential)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Describes the cluster for `KUBERNETES_EXEC_INFO` the way client-go does,
+/// leaving out the keys that are not set
+fn cluster_info(cluster: &Cluster) -> Result<serde_json::Value> {
+    let mut info = serde_json::Map::new();
+    info.insert("server".to_string(), cluster.server.clone().into());
+    if let Some(tls_server_name) = &cluster.tls_server_name {
+        info.insert(
+            "tls-server-name".to_string(),
+            tls_server_name.clone().into(),
+        );
+    }
+    if cluster.insecure_skip_tls_verify {
+        info.insert("insecure-skip-tls-verify".to_string(), true.into());
+    }
+    if let Some(ca_pem) = cluster.ca_pem()? {
+        info.insert(
+            "certificate-authority-data".to_string(),
+            STANDARD.encode(ca_pem).into(),
+        );
+    }
+    if let Some(proxy_url) = &cluster.proxy_url {
+        info.insert("proxy-url".to_string(), proxy_url.as_str().into());
+    }
+    if cluster.disable_compression {
+        info.insert("disable-compression".to_string(), true.into());
+    }
+    // Per-cluster configuration for the plugin, see the kubernetes docs
+    if let Some(config) = cluster.extensions.get_value(EXEC_EXTENSION) {
+        info.insert("config".to_string(), serde_json::to_value(config)?);
+    }
+    Ok(info.into())
+}
+
+/// A 64 bit FNV-1a hash, used for file names that stay the same between runs
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
@@ -0,0 +1,123 @@
+//! The module holding the `Extensions` and `NamedExtension` structs
+
+use crate::errors::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::slice::Iter;
+
+/// An entry of an `extensions` list. Tools like minikube use these to store
+/// their own data in a kubeconfig.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NamedExtension {
+    /// The name of the extension, usually the tool that owns it
+    pub name: String,
+
+    /// The data of the extension, in a format decided by its owner
+    pub extension: Value,
+}
+
+/// An `extensions` list, found on the config itself, its preferences and on
+/// each cluster, context and user. The order of the list is kept when it is
+/// written back.
+///
+/// ```
+/// use kube_conf::Config;
+/// # use kube_conf::errors::*;
+/// #[derive(serde_derive::Deserialize)]
+/// struct ClusterInfo {
+///     provider: String,
+/// }
+///
+/// let config = Config::load("tests/config-cluster-options.yml")?;
+/// let info: ClusterInfo = config.clusters[0]
+///     .extensions
+///     .get("cluster_info")?
+///     .unwrap();
+///
+/// assert_eq!(info.provider, "minikube.sigs.k8s.io");
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Extensions(Vec<NamedExtension>);
+
+impl Extensions {
+    /// Creates an empty list
+    pub fn new() -> Self {
+        Extensions(Vec::new())
+    }
+
+    /// Decodes the extension with the given name, or returns `None` when there
+    /// is none. An error is returned when it does not decode as a `T`.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        match self.get_value(name) {
+            Some(value) => Ok(Some(serde_yaml::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the raw data of the extension with the given name
+    pub fn get_value(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find(|e| e.name == name).map(|e| &e.extension)
+    }
+
+    /// Sets the extension with the given name, replacing it in place if it
+    /// exists and adding it to the end otherwise.
+    pub fn insert<T: Serialize>(&mut self, name: &str, extension: &T) -> Result<()> {
+        let extension = serde_yaml::to_value(extension)?;
+        match self.0.iter_mut().find(|e| e.name == name) {
+            Some(existing) => existing.extension = extension,
+            None => self.0.push(NamedExtension {
+                name: name.to_string(),
+                extension,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Removes the extension with the given name, returning its data
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        let index = self.0.iter().position(|e| e.name == name)?;
+        Some(self.0.remove(index).extension)
+    }
+
+    /// Adds the extensions of `other` whose names are not in the list yet
+    pub(crate) fn merge(&mut self, other: Extensions) {
+        for extension in other.0 {
+            if self.get_value(&extension.name).is_none() {
+                self.0.push(extension);
+            }
+        }
+    }
+
+    /// Iterates over the extensions in order
+    pub fn iter(&self) -> Iter<'_, NamedExtension> {
+        self.0.iter()
+    }
+
+    /// The number of extensions
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no extensions
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Extensions {
+    type Item = &'a NamedExtension;
+    type IntoIter = Iter<'a, NamedExtension>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl From<Vec<NamedExtension>> for Extensions {
+    fn from(extensions: Vec<NamedExtension>) -> Self {
+        Extensions(extensions)
+    }
+}
@@ -1,4 +1,4 @@
-use serde::de::Error;
+use serde::de::{DeserializeOwned, Error};
 use serde_yaml::Mapping;
 use serde_yaml::Value;
 
@@ -40,3 +40,48 @@ where
         .cloned()
         .ok_or_else(|| E::missing_field("Field could not be parsed as mapping"))
 }
+
+/// What a known key is parsed as, to tell whether its value could be parsed
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    /// Parsed with `get_string`, skipping values that are not strings
+    String,
+    /// Parsed with `get_bool`, skipping values that are not bools
+    Bool,
+    /// Parsed in a way that fails when the value can not be parsed
+    Other,
+}
+
+/// Gets the keys that are not known, along with the known keys whose value
+/// was skipped because it has the wrong type, so that no value is lost when
+/// the mapping is written back
+pub fn get_remainder(map: &Mapping, known_keys: &[(&str, Kind)]) -> Mapping {
+    map.iter()
+        .filter(|(key, value)| {
+            let kind = known_keys
+                .iter()
+                .find(|(known, _)| key.as_str() == Some(*known))
+                .map(|(_, kind)| kind);
+            match kind {
+                None => true,
+                Some(Kind::String) => !value.is_string(),
+                Some(Kind::Bool) => !value.is_bool(),
+                Some(Kind::Other) => false,
+            }
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+pub fn get_optional<T, E>(map: &Mapping, key: &str) -> Result<Option<T>, E>
+where
+    T: DeserializeOwned,
+    E: Error,
+{
+    let key_val = Value::String(String::from(key));
+
+    map.get(&key_val)
+        .map(|value| serde_yaml::from_value(value.clone()))
+        .transpose()
+        .map_err(|e| E::custom(format!("Field {} could not be parsed: {}", key, e)))
+}
@@ -0,0 +1,542 @@
+//! Building HTTP clients for talking to the cluster of a context
+
+use crate::cluster::Cluster;
+use crate::errors::*;
+use crate::exec::ExecCredential;
+use crate::user::User;
+use crate::{Config, Context};
+
+#[cfg(feature = "reqwest")]
+pub use self::reqwest_client::HttpClient;
+#[cfg(feature = "tower")]
+pub use self::tower_auth::{AuthLayer, AuthService, BoxError};
+
+/// Finds the context with the given name, along with its cluster and user
+fn lookup<'a>(config: &'a Config, context_name: &str) -> Result<(&'a Context, Cluster, User)> {
+    let context = config
+        .context(context_name)
+        .ok_or_else(|| ErrorKind::UnknownContext(context_name.to_string()))?;
+    let cluster = context
+        .get_cluster(config)
+        .ok_or_else(|| ErrorKind::UnknownCluster(context.cluster.clone()))?;
+    let user = context
+        .get_user(config)
+        .ok_or_else(|| ErrorKind::UnknownUser(context.user.clone()))?;
+
+    Ok((context, cluster, user))
+}
+
+/// Gets the PEM encoded client certificate and key, taking the ones from the
+/// exec plugin over the ones in the config
+fn client_identity(
+    user: &User,
+    exec_credential: Option<&ExecCredential>,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    if let Some(credential) = exec_credential {
+        if let (Some(cert), Some(key)) = (
+            credential.client_certificate_data(),
+            credential.client_key_data(),
+        ) {
+            return Ok(Some((cert.into(), key.into())));
+        }
+    }
+    match (user.client_cert_pem()?, user.client_key_pem()?) {
+        (Some(cert), Some(key)) => Ok(Some((cert, key))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(feature = "reqwest")]
+mod reqwest_client {
+    use crate::errors::*;
+    use crate::exec::ExecCredentialCache;
+    use crate::tls::client_config;
+    use crate::Config;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, HOST};
+    use reqwest::{Client, Method, Proxy, RequestBuilder, Url};
+    use std::sync::Arc;
+    use url::Position;
+
+    /// A `reqwest::Client` set up for the cluster of a context, together with
+    /// the address of the cluster. Build one with
+    /// [`Config::http_client`](../struct.Config.html#method.http_client).
+    #[derive(Debug, Clone)]
+    pub struct HttpClient {
+        /// The client, with the TLS settings and the credentials of the user
+        /// already applied
+        pub client: Client,
+
+        /// The `server` of the cluster. Request paths are relative to it.
+        pub server: Url,
+
+        /// The URL requests are sent to, which has `tls-server-name` as the
+        /// host when it is set
+        base: Url,
+    }
+
+    impl HttpClient {
+        /// Starts a request to the given path on the cluster, i.e.
+        /// `/api/v1/namespaces`.
+        ///
+        /// Requests started this way send the `tls-server-name` of the cluster
+        /// as SNI. Requests sent with `client` to `server` directly send the
+        /// host of the server instead, but the certificate of the server is
+        /// still verified against `tls-server-name`.
+        pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+            let url = format!(
+                "{}/{}",
+                self.base.as_str().trim_end_matches('/'),
+                path.trim_start_matches('/')
+            );
+            self.client.request(method, url)
+        }
+
+        /// Starts a GET request to the given path on the cluster.
+        pub fn get(&self, path: &str) -> RequestBuilder {
+            self.request(Method::GET, path)
+        }
+    }
+
+    impl Config {
+        /// Builds an HTTP client for the cluster of the given context.
+        ///
+        /// The client uses the TLS settings from
+        /// [`Context::rustls_config`](context/struct.Context.html#method.rustls_config),
+        /// sends the bearer token or basic auth credentials and the
+        /// impersonation headers of the user and goes through the `proxy-url`
+        /// of the cluster when set. The token and client certificate of an exec
+        /// plugin take the place of the ones in the config.
+        ///
+        /// Exec plugins are run when the client is built, and the token or
+        /// client certificate they return is used for the lifetime of the
+        /// client. Build a new client when they expire, or use
+        /// [`http_client_with_exec_cache`](#method.http_client_with_exec_cache)
+        /// to only run the plugin again when needed. A `tokenFile` is also
+        /// only read when the client is built.
+        ///
+        /// When the cluster has `tls-server-name` set, it is sent in the SNI
+        /// extension like client-go does. `reqwest` always sends the host of
+        /// the URL, so [`HttpClient::request`](http/struct.HttpClient.html#method.request)
+        /// uses `tls-server-name` as the host, which the client resolves to
+        /// the addresses of the server for every new connection, and sends
+        /// the server as the `Host` header. Through a `proxy-url` the host of
+        /// the server is sent instead.
+        pub fn http_client(&self, context_name: &str) -> Result<HttpClient> {
+            self.http_client_with_exec_cache(context_name, &ExecCredentialCache::new())
+        }
+
+        /// Like [`http_client`](#method.http_client), taking the credentials
+        /// of exec plugins from the given cache.
+        pub fn http_client_with_exec_cache(
+            &self,
+            context_name: &str,
+            exec_cache: &ExecCredentialCache,
+        ) -> Result<HttpClient> {
+            let (_, cluster, user) = super::lookup(self, context_name)?;
+            let exec_credential = match &user.exec {
+                Some(exec) => Some(exec_cache.get_with_cluster(exec, Some(&cluster))?),
+                None => None,
+            };
+
+            let server = Url::parse(&cluster.server)
+                .chain_err(|| ErrorKind::InvalidUrl(cluster.server.clone()))?;
+
+            let mut base = server.clone();
+            let mut headers = HeaderMap::new();
+            let mut resolver = None;
+            if let (Some(tls_server_name), None) = (&cluster.tls_server_name, &cluster.proxy_url) {
+                let host =
+                    HeaderValue::from_str(&server[Position::BeforeHost..Position::AfterPort])
+                        .chain_err(|| ErrorKind::InvalidUrl(cluster.server.clone()))?;
+                headers.insert(HOST, host);
+                base.set_host(Some(tls_server_name)).chain_err(|| {
+                    ErrorKind::Tls(format!("invalid tls-server-name '{}'", tls_server_name))
+                })?;
+                resolver = Some(ServerNameResolver {
+                    tls_server_name: tls_server_name.clone(),
+                    host: server
+                        .host_str()
+                        .unwrap_or_default()
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .to_string(),
+                });
+            }
+
+            let token = match (user.token_source(), &exec_credential) {
+                (Some(source), _) => Some(source.current_token()?),
+                (None, Some(credential)) => credential.token().map(str::to_string),
+                (None, None) => user
+                    .auth_provider
+                    .as_ref()
+                    .and_then(|a| a.token())
+                    .map(str::to_string),
+            };
+            let authorization = match (token, &user.username, &user.password) {
+                (Some(token), _, _) => Some(format!("Bearer {}", token)),
+                (None, Some(username), Some(password)) => Some(format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", username, password))
+                )),
+                _ => None,
+            };
+            if let Some(authorization) = authorization {
+                let mut value = HeaderValue::from_str(&authorization)
+                    .chain_err(|| "credentials can not be sent in a header")?;
+                value.set_sensitive(true);
+                headers.insert(AUTHORIZATION, value);
+            }
+            for (name, value) in user
+                .impersonation()
+                .map(|i| i.headers())
+                .unwrap_or_default()
+            {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .chain_err(|| "impersonation can not be sent in a header")?;
+                let value = HeaderValue::from_str(&value)
+                    .chain_err(|| "impersonation can not be sent in a header")?;
+                headers.append(name, value);
+            }
+
+            let identity = super::client_identity(&user, exec_credential.as_ref())?;
+
+            let mut builder = Client::builder()
+                .use_preconfigured_tls(client_config(&cluster, identity)?)
+                .default_headers(headers);
+
+            if let Some(resolver) = resolver {
+                builder = builder.dns_resolver(Arc::new(resolver));
+            }
+
+            if let Some(proxy_url) = cluster.proxy()? {
+                let proxy = Proxy::all(proxy_url.clone())
+                    .chain_err(|| ErrorKind::InvalidUrl(proxy_url.to_string()))?;
+                builder = builder.proxy(proxy);
+            }
+
+            let client = builder
+                .build()
+                .chain_err(|| "could not build http client")?;
+
+            Ok(HttpClient {
+                client,
+                server,
+                base,
+            })
+        }
+    }
+
+    /// Resolves the `tls-server-name` of a cluster to the addresses of its
+    /// server, looking them up again for every new connection
+    struct ServerNameResolver {
+        tls_server_name: String,
+        host: String,
+    }
+
+    impl Resolve for ServerNameResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            let host = if name.as_str() == self.tls_server_name {
+                self.host.clone()
+            } else {
+                name.as_str().to_string()
+            };
+            Box::pin(async move {
+                let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?;
+                Ok(Box::new(addrs.collect::<Vec<_>>().into_iter()) as Addrs)
+            })
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+mod tower_auth {
+    use crate::cluster::Cluster;
+    use crate::errors::*;
+    use crate::exec::ExecCredentialCache;
+    use crate::tls::client_config;
+    use crate::token::TokenSource;
+    use crate::user::User;
+    use crate::{Config, Context};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use error_chain::ChainedError;
+    use http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+    use http::Request;
+    use hyper_rustls::{FixedServerNameResolver, HttpsConnector, HttpsConnectorBuilder};
+    use hyper_util::client::legacy::connect::HttpConnector;
+    use rustls::pki_types::ServerName;
+    use std::convert::TryFrom;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::Poll;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    /// The error returned by an `AuthService`, either from getting the
+    /// credentials or from the wrapped service
+    pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    /// A `tower::Layer` adding the `Authorization` header and the
+    /// `Impersonate-*` headers for a user to every request. Build one with
+    /// [`Config::auth_layer`](../struct.Config.html#method.auth_layer).
+    ///
+    /// The header is made from, in order of preference, the
+    /// [`TokenSource`](../token/struct.TokenSource.html) of the user, the exec
+    /// plugin, the auth-provider and basic auth. Tokens from a `tokenFile` are
+    /// read again when the file changes or every minute, and exec plugin
+    /// credentials are taken from an `ExecCredentialCache`, which runs the
+    /// plugin again when they expire. The plugin is run and the file is read
+    /// on the blocking threads of the `tokio` runtime.
+    ///
+    /// Client certificates are sent by the connector instead, see
+    /// [`Context::https_connector`](../context/struct.Context.html#method.https_connector).
+    #[derive(Clone)]
+    pub struct AuthLayer {
+        credentials: Arc<Credentials>,
+    }
+
+    /// The service made by an `AuthLayer`
+    #[derive(Clone)]
+    pub struct AuthService<S> {
+        credentials: Arc<Credentials>,
+        inner: S,
+    }
+
+    #[derive(Clone)]
+    struct Credentials {
+        user: User,
+        cluster: Option<Cluster>,
+        exec_cache: Arc<ExecCredentialCache>,
+        token_source: Option<TokenSource>,
+    }
+
+    impl AuthLayer {
+        /// Creates a layer for the given user. The cluster is passed along to
+        /// exec plugins that ask for cluster info.
+        pub fn new(user: User, cluster: Option<Cluster>) -> Self {
+            AuthLayer {
+                credentials: Arc::new(Credentials {
+                    token_source: user.token_source(),
+                    user,
+                    cluster,
+                    exec_cache: Arc::new(ExecCredentialCache::new()),
+                }),
+            }
+        }
+
+        /// Uses the given cache for exec plugin credentials instead of one
+        /// private to this layer, i.e. to share them between clients.
+        pub fn with_exec_cache(mut self, exec_cache: Arc<ExecCredentialCache>) -> Self {
+            Arc::make_mut(&mut self.credentials).exec_cache = exec_cache;
+            self
+        }
+
+        /// Uses the given token source instead of the one from
+        /// [`User::token_source`](../user/struct.User.html#method.token_source),
+        /// i.e. to read the token file at another interval.
+        pub fn with_token_source(mut self, token_source: TokenSource) -> Self {
+            Arc::make_mut(&mut self.credentials).token_source = Some(token_source);
+            self
+        }
+
+        /// Gets the current value of the `Authorization` header, or `None`
+        /// when the user has no credentials that can be sent in a header.
+        pub fn authorization(&self) -> Result<Option<HeaderValue>> {
+            self.credentials.authorization()
+        }
+    }
+
+    impl<S> Layer<S> for AuthLayer {
+        type Service = AuthService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            AuthService {
+                credentials: self.credentials.clone(),
+                inner,
+            }
+        }
+    }
+
+    impl<S, B> Service<Request<B>> for AuthService<S>
+    where
+        S: Service<Request<B>> + Clone + Send + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: Send + 'static,
+    {
+        type Response = S::Response;
+        type Error = BoxError;
+        type Future =
+            Pin<Box<dyn Future<Output = std::result::Result<S::Response, BoxError>> + Send>>;
+
+        fn poll_ready(
+            &mut self,
+            cx: &mut std::task::Context<'_>,
+        ) -> Poll<std::result::Result<(), BoxError>> {
+            self.inner.poll_ready(cx).map_err(Into::into)
+        }
+
+        fn call(&mut self, mut request: Request<B>) -> Self::Future {
+            // The service that was polled ready is the one that has to be
+            // called, so it is taken along and a clone is left in its place
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+            let credentials = self.credentials.clone();
+
+            Box::pin(async move {
+                // Running exec plugins and reading token files blocks, so it
+                // is kept off the threads of the runtime
+                let headers = tokio::task::spawn_blocking(move || {
+                    credentials
+                        .headers()
+                        .map_err(|e| e.display_chain().to_string())
+                })
+                .await??;
+
+                headers.apply(request.headers_mut());
+
+                inner.call(request).await.map_err(Into::into)
+            })
+        }
+    }
+
+    /// The headers the credentials of a user add to a request
+    struct Headers {
+        authorization: Option<HeaderValue>,
+        impersonation: Vec<(HeaderName, HeaderValue)>,
+    }
+
+    impl Headers {
+        fn apply(self, headers: &mut HeaderMap) {
+            if let Some(value) = self.authorization {
+                headers.insert(AUTHORIZATION, value);
+            }
+            for (name, value) in self.impersonation {
+                headers.append(name, value);
+            }
+        }
+    }
+
+    impl Credentials {
+        /// Gets the `Authorization` header and the `Impersonate-*` headers
+        fn headers(&self) -> Result<Headers> {
+            let authorization = self.authorization()?;
+            let mut impersonation = Vec::new();
+            for (name, value) in self
+                .user
+                .impersonation()
+                .map(|i| i.headers())
+                .unwrap_or_default()
+            {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .chain_err(|| "impersonation can not be sent in a header")?;
+                let value = HeaderValue::from_str(&value)
+                    .chain_err(|| "impersonation can not be sent in a header")?;
+                impersonation.push((name, value));
+            }
+            Ok(Headers {
+                authorization,
+                impersonation,
+            })
+        }
+
+        fn authorization(&self) -> Result<Option<HeaderValue>> {
+            let user = &self.user;
+            let authorization = if let Some(token_source) = &self.token_source {
+                Some(format!("Bearer {}", token_source.current_token()?))
+            } else if let Some(exec) = &user.exec {
+                let credential = self
+                    .exec_cache
+                    .get_with_cluster(exec, self.cluster.as_ref())?;
+                // A client certificate from the plugin is sent by the
+                // connector, see `Context::https_connector_with_exec_cache`
+                credential.token().map(|token| format!("Bearer {}", token))
+            } else if let Some(token) = user.auth_provider.as_ref().and_then(|a| a.token()) {
+                Some(format!("Bearer {}", token))
+            } else if let (Some(username), Some(password)) = (&user.username, &user.password) {
+                Some(format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", username, password))
+                ))
+            } else {
+                None
+            };
+
+            match authorization {
+                Some(authorization) => {
+                    let mut value = HeaderValue::from_str(&authorization)
+                        .chain_err(|| "credentials can not be sent in a header")?;
+                    value.set_sensitive(true);
+                    Ok(Some(value))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+
+    impl Config {
+        /// Builds an `AuthLayer` for the user of the given context.
+        pub fn auth_layer(&self, context_name: &str) -> Result<AuthLayer> {
+            let (_, cluster, user) = super::lookup(self, context_name)?;
+            Ok(AuthLayer::new(user, Some(cluster)))
+        }
+    }
+
+    impl Context {
+        /// Builds a hyper connector for the cluster of this context, using the
+        /// TLS settings from
+        /// [`rustls_config`](#method.rustls_config). When the cluster has
+        /// `tls-server-name` set, it is sent in the SNI extension.
+        ///
+        /// When the user has an exec plugin, it is run and the client
+        /// certificate it returns is used for the lifetime of the connector.
+        /// Build a new connector when it expires, or use
+        /// [`https_connector_with_exec_cache`](#method.https_connector_with_exec_cache)
+        /// to only run the plugin again when needed.
+        pub fn https_connector(&self, config: &Config) -> Result<HttpsConnector<HttpConnector>> {
+            self.https_connector_with_exec_cache(config, &ExecCredentialCache::new())
+        }
+
+        /// Like [`https_connector`](#method.https_connector), taking the
+        /// credentials of exec plugins from the given cache.
+        pub fn https_connector_with_exec_cache(
+            &self,
+            config: &Config,
+            exec_cache: &ExecCredentialCache,
+        ) -> Result<HttpsConnector<HttpConnector>> {
+            let cluster = self
+                .get_cluster(config)
+                .ok_or_else(|| ErrorKind::UnknownCluster(self.cluster.clone()))?;
+            let user = self
+                .get_user(config)
+                .ok_or_else(|| ErrorKind::UnknownUser(self.user.clone()))?;
+            let exec_credential = match &user.exec {
+                Some(exec) => Some(exec_cache.get_with_cluster(exec, Some(&cluster))?),
+                None => None,
+            };
+            let identity = super::client_identity(&user, exec_credential.as_ref())?;
+
+            let mut http = HttpConnector::new();
+            http.enforce_http(false);
+
+            let builder = HttpsConnectorBuilder::new()
+                .with_tls_config(client_config(&cluster, identity)?)
+                .https_or_http();
+            let builder = match &cluster.tls_server_name {
+                Some(tls_server_name) => {
+                    let name = ServerName::try_from(tls_server_name.clone()).chain_err(|| {
+                        ErrorKind::Tls(format!("invalid tls-server-name '{}'", tls_server_name))
+                    })?;
+                    builder.with_server_name_resolver(FixedServerNameResolver::new(name))
+                }
+                None => builder,
+            };
+
+            Ok(builder.enable_http1().wrap_connector(http))
+        }
+    }
+}
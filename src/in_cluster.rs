@@ -0,0 +1,146 @@
+//! Building a config for a pod from its service account, the way client-go
+//! does for in-cluster clients
+
+use crate::errors::*;
+use crate::extension::Extensions;
+use crate::{Cluster, Config, Context, User};
+use serde_yaml::Mapping;
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// The directory the service account of a pod is mounted at
+const SERVICE_ACCOUNT_ROOT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// The name used for the cluster, context and user of an in-cluster config
+const IN_CLUSTER: &str = "in-cluster";
+
+impl Config {
+    /// Builds a config for talking to the cluster a pod is running in.
+    ///
+    /// The address of the API server is taken from the
+    /// `KUBERNETES_SERVICE_HOST` and `KUBERNETES_SERVICE_PORT` environment
+    /// variables, and the token, certificate authority and namespace from the
+    /// service account mounted at
+    /// `/var/run/secrets/kubernetes.io/serviceaccount`. The config has a single
+    /// cluster, user and context named `in-cluster`.
+    ///
+    /// The user has both `token` and `tokenFile` set, so that the
+    /// [`TokenSource`](token/struct.TokenSource.html) of the user picks up
+    /// the token when it is rotated.
+    pub fn in_cluster() -> Result<Config> {
+        Config::in_cluster_with_root(SERVICE_ACCOUNT_ROOT)
+    }
+
+    /// Like [`Config::in_cluster`](#method.in_cluster), reading the service
+    /// account from the given directory instead.
+    pub fn in_cluster_with_root<P: AsRef<Path>>(root: P) -> Result<Config> {
+        let root = root.as_ref();
+        let host = env::var("KUBERNETES_SERVICE_HOST")
+            .chain_err(|| ErrorKind::NotInCluster("KUBERNETES_SERVICE_HOST is not set".into()))?;
+        let port = env::var("KUBERNETES_SERVICE_PORT")
+            .chain_err(|| ErrorKind::NotInCluster("KUBERNETES_SERVICE_PORT is not set".into()))?;
+
+        // IPv6 addresses need brackets in a url
+        let server = if host.contains(':') {
+            format!("https://[{}]:{}", host, port)
+        } else {
+            format!("https://{}:{}", host, port)
+        };
+
+        let token_path = root.join("token");
+        let token = read_to_string(&token_path).chain_err(|| {
+            ErrorKind::NotInCluster(format!("could not read '{}'", token_path.display()))
+        })?;
+
+        let ca_path = root.join("ca.crt");
+        let namespace = read_to_string(root.join("namespace"))
+            .ok()
+            .map(|namespace| namespace.trim().to_string())
+            .filter(|namespace| !namespace.is_empty());
+
+        let mut conf = Config {
+            api_version: Some("v1".to_string()),
+            clusters: vec![Cluster {
+                name: IN_CLUSTER.to_string(),
+                server,
+                certificate_authority: Some(ca_path).filter(|path| path.exists()),
+                certificate_authority_data: None,
+                insecure_skip_tls_verify: false,
+                proxy_url: None,
+                tls_server_name: None,
+                disable_compression: false,
+                extensions: Extensions::new(),
+                extra: Mapping::new(),
+                entry_extra: Mapping::new(),
+            }],
+            contexts: vec![Context {
+                name: IN_CLUSTER.to_string(),
+                cluster: IN_CLUSTER.to_string(),
+                namespace,
+                user: IN_CLUSTER.to_string(),
+                extensions: Extensions::new(),
+                extra: Mapping::new(),
+                entry_extra: Mapping::new(),
+            }],
+            current_context: Some(IN_CLUSTER.to_string()),
+            extensions: Extensions::new(),
+            kind: Some("Config".to_string()),
+            preferences: None,
+            users: vec![User {
+                name: IN_CLUSTER.to_string(),
+                token: Some(token.trim().to_string()),
+                token_file: Some(token_path),
+                username: None,
+                password: None,
+                client_certificate: None,
+                client_certificate_data: None,
+                client_key: None,
+                client_key_data: None,
+                exec: None,
+                auth_provider: None,
+                impersonate: None,
+                impersonate_uid: None,
+                impersonate_groups: None,
+                impersonate_user_extra: None,
+                extensions: Extensions::new(),
+                extra: Mapping::new(),
+                entry_extra: Mapping::new(),
+            }],
+            extra: Mapping::new(),
+            source: None,
+            resolved: false,
+            original_paths: Default::default(),
+            index: Default::default(),
+            origins: Default::default(),
+        };
+        conf.reindex();
+
+        Ok(conf)
+    }
+
+    /// Finds a config the way client-go does.
+    ///
+    /// The kubeconfig from [`Config::load_default`](#method.load_default) is
+    /// used when it exists, otherwise the in-cluster config from
+    /// [`Config::in_cluster`](#method.in_cluster), also when neither
+    /// `$KUBECONFIG` nor `$HOME` are set. When neither is available the error
+    /// for the missing kubeconfig is returned.
+    pub fn infer() -> Result<Config> {
+        match Config::load_default() {
+            Err(Error(ErrorKind::MissingConfigFile(_), _))
+            | Err(Error(ErrorKind::NoConfigLocation, _))
+                if is_in_cluster() =>
+            {
+                Config::in_cluster()
+            }
+            result => result,
+        }
+    }
+}
+
+/// Whether this process looks like it is running in a pod
+fn is_in_cluster() -> bool {
+    env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+        && env::var_os("KUBERNETES_SERVICE_PORT").is_some()
+}
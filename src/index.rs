@@ -0,0 +1,138 @@
+//! Looking up clusters, contexts and users by name without scanning the
+//! whole config
+
+use crate::{Cluster, Config, Context, User};
+use std::collections::HashMap;
+
+/// The position of each cluster, context and user in the lists of a config,
+/// by name. Only the first entry with a name is indexed, like only the first
+/// one is used by `kubectl`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Index {
+    pub(crate) clusters: HashMap<String, usize>,
+    pub(crate) contexts: HashMap<String, usize>,
+    pub(crate) users: HashMap<String, usize>,
+}
+
+impl Index {
+    fn build(config: &Config) -> Index {
+        Index {
+            clusters: positions(config.clusters.iter().map(|c| &c.name)),
+            contexts: positions(config.contexts.iter().map(|c| &c.name)),
+            users: positions(config.users.iter().map(|u| &u.name)),
+        }
+    }
+}
+
+impl Config {
+    /// Gets the cluster with the given name.
+    pub fn cluster(&self, name: &str) -> Option<&Cluster> {
+        lookup(&self.clusters, &self.index.clusters, name, |c| &c.name)
+    }
+
+    /// Gets the context with the given name.
+    pub fn context(&self, name: &str) -> Option<&Context> {
+        lookup(&self.contexts, &self.index.contexts, name, |c| &c.name)
+    }
+
+    /// Gets the user with the given name.
+    pub fn user(&self, name: &str) -> Option<&User> {
+        lookup(&self.users, &self.index.users, name, |u| &u.name)
+    }
+
+    /// Rebuilds the index used by [`cluster`](#method.cluster),
+    /// [`context`](#method.context) and [`user`](#method.user).
+    ///
+    /// Lookups stay correct when the `clusters`, `contexts` or `users` lists
+    /// are changed directly, but fall back to scanning the list for names the
+    /// index does not know about. Calling this after such changes makes them
+    /// fast again.
+    pub fn reindex(&mut self) {
+        self.index = Index::build(self);
+    }
+
+    /// Adds the clusters, contexts and users whose names are not in this
+    /// config yet.
+    pub(crate) fn merge_entries(
+        &mut self,
+        clusters: Vec<Cluster>,
+        contexts: Vec<Context>,
+        users: Vec<User>,
+    ) {
+        self.reindex();
+        add_new(
+            &mut self.clusters,
+            &mut self.index.clusters,
+            clusters,
+            |c| &c.name,
+        );
+        add_new(
+            &mut self.contexts,
+            &mut self.index.contexts,
+            contexts,
+            |c| &c.name,
+        );
+        add_new(&mut self.users, &mut self.index.users, users, |u| &u.name);
+    }
+}
+
+fn positions<'a, I>(names: I) -> HashMap<String, usize>
+where
+    I: Iterator<Item = &'a String>,
+{
+    let mut positions = HashMap::new();
+    for (position, name) in names.enumerate() {
+        positions.entry(name.clone()).or_insert(position);
+    }
+    positions
+}
+
+/// Finds the entry with the given name through the index, checking that the
+/// index is still right and scanning the list when it is not.
+fn lookup<'a, T, F>(
+    entries: &'a [T],
+    index: &HashMap<String, usize>,
+    name: &str,
+    name_of: F,
+) -> Option<&'a T>
+where
+    F: Fn(&T) -> &String,
+{
+    position(entries, index, name, name_of).map(|position| &entries[position])
+}
+
+/// Finds the position of the entry with the given name, like `lookup`.
+pub(crate) fn position<T, F>(
+    entries: &[T],
+    index: &HashMap<String, usize>,
+    name: &str,
+    name_of: F,
+) -> Option<usize>
+where
+    F: Fn(&T) -> &String,
+{
+    match index.get(name) {
+        Some(position) if entries.get(*position).is_some_and(|e| name_of(e) == name) => {
+            Some(*position)
+        }
+        _ => entries.iter().position(|entry| name_of(entry) == name),
+    }
+}
+
+/// Adds the entries whose names are not in the index yet, keeping the index up
+/// to date
+fn add_new<T, F>(
+    entries: &mut Vec<T>,
+    index: &mut HashMap<String, usize>,
+    new_entries: Vec<T>,
+    name_of: F,
+) where
+    F: Fn(&T) -> &String,
+{
+    for entry in new_entries {
+        if !index.contains_key(name_of(&entry)) {
+            index.insert(name_of(&entry).clone(), entries.len());
+            entries.push(entry);
+        }
+    }
+}
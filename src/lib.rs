@@ -30,6 +30,43 @@
 //! assert_eq!("dev-frontend", current_context);
 //! # Ok::<(), kube_conf::errors::Error>(())
 //! ```
+//!
+//! ## Running inside a pod
+//!
+//! `Config::infer` uses the kubeconfig when there is one, and otherwise builds
+//! a config from the service account of the pod, like client-go does.
+//!
+//! ```no_run
+//! use kube_conf::Config;
+//! let config = Config::infer()?;
+//! # Ok::<(), kube_conf::errors::Error>(())
+//! ```
+//!
+//! ## Writing a config
+//!
+//! ```
+//! use kube_conf::Config;
+//! let mut config = Config::load("tests/config.yml")?;
+//! config.current_context = Some("exp-scratch".to_string());
+//! let yaml = config.to_yaml_string()?;
+//!
+//! assert!(yaml.contains("current-context: exp-scratch"));
+//! # Ok::<(), kube_conf::errors::Error>(())
+//! ```
+//!
+//! # Cargo features
+//!
+//! - `oidc`: Refreshing expired `oidc` auth-provider tokens with
+//!   `Config::refresh_oidc_token`
+//! - `rustls`: Building a `rustls::ClientConfig` for a context with
+//!   `Context::rustls_config`
+//! - `native-tls`: Building a `native_tls::TlsConnector` for a context with
+//!   `Context::native_tls_connector`
+//! - `reqwest`: Building a `reqwest::Client` for a context with
+//!   `Config::http_client`
+//! - `tower`: A `tower::Layer` adding the credentials of a user to requests
+//!   with `Config::auth_layer`, and a hyper connector for a context with
+//!   `Context::https_connector`
 
 // `error_chain!` can recurse deeply
 #![recursion_limit = "1024"]
@@ -40,10 +77,28 @@ extern crate error_chain;
 extern crate serde_derive;
 extern crate serde_yaml;
 
+pub mod auth_provider;
 pub mod cluster;
 pub mod context;
+mod data;
+pub mod document;
+pub mod edit;
+pub mod exec;
+pub mod extension;
 mod get;
+#[cfg(any(feature = "reqwest", feature = "tower"))]
+pub mod http;
+mod in_cluster;
+mod index;
+mod lock;
+mod origin;
+mod paths;
+pub mod preferences;
+mod set;
+mod tls;
+pub mod token;
 pub mod user;
+mod yaml;
 
 /// A module that exposes errors thrown by the crate.
 ///
@@ -58,6 +113,8 @@ pub mod errors {
     error_chain! {
         foreign_links {
             Yaml(serde_yaml::Error) #[doc = "Error when parsing a yaml file"];
+            Io(std::io::Error) #[doc = "Error when reading or writing a file"];
+            Json(serde_json::Error) #[doc = "Error when parsing json"];
         }
         errors {
             /// If a config file does not exist (at the given path) this error
@@ -66,6 +123,106 @@ pub mod errors {
                 description("config file was not found at the specified path"),
                 display("missing config file: '{}'", f),
             }
+
+            /// If neither `$KUBECONFIG` nor `$HOME` are set, so there is no
+            /// default location to look for the config at, this error will be
+            /// returned
+            NoConfigLocation {
+                description("there is no default location for the config file"),
+                display("Neither $KUBECONFIG nor $HOME are defined"),
+            }
+
+            /// If an exec credential plugin could not be run or returned
+            /// something unexpected this error will be returned
+            ExecPlugin(command: String, reason: String) {
+                description("exec credential plugin failed"),
+                display("exec plugin '{}' failed: {}", command, reason),
+            }
+
+            /// If a credential file referenced by the config can not be read
+            /// this error will be returned
+            UnreadableFile(f: String) {
+                description("credential file could not be read"),
+                display("could not read file: '{}'", f),
+            }
+
+            /// If inline credential data is not valid base64 this error will
+            /// be returned
+            InvalidBase64(key: String) {
+                description("credential data is not valid base64"),
+                display("invalid base64 in '{}'", key),
+            }
+
+            /// If a credential is given both as a file and as inline data this
+            /// error will be returned
+            ConflictingCredentials(key: String) {
+                description("credential is given both as a file and as data"),
+                display("both '{0}' and '{0}-data' are set", key),
+            }
+
+            /// If a context refers to a cluster that is not in the config this
+            /// error will be returned
+            UnknownCluster(name: String) {
+                description("cluster was not found in the config"),
+                display("cluster '{}' was not found in the config", name),
+            }
+
+            /// If a context refers to a user that is not in the config this
+            /// error will be returned
+            UnknownUser(name: String) {
+                description("user was not found in the config"),
+                display("user '{}' was not found in the config", name),
+            }
+
+            /// If a context that is not in the config is asked for this error
+            /// will be returned
+            UnknownContext(name: String) {
+                description("context was not found in the config"),
+                display("context '{}' was not found in the config", name),
+            }
+
+            /// If a context is renamed to the name of another context this
+            /// error will be returned
+            ContextExists(name: String) {
+                description("context already exists in the config"),
+                display("context '{}' already exists in the config", name),
+            }
+
+            /// If a config file stays locked by another writer for too long
+            /// this error will be returned
+            ConfigLocked(f: String) {
+                description("config file is locked by another writer"),
+                display("timed out waiting for the lock on config file: '{}'", f),
+            }
+
+            /// If a url in the config can not be parsed this error will be
+            /// returned
+            InvalidUrl(url: String) {
+                description("url could not be parsed"),
+                display("invalid url: '{}'", url),
+            }
+
+            /// If a TLS configuration can not be built from the credentials in
+            /// the config this error will be returned
+            Tls(reason: String) {
+                description("tls configuration could not be built"),
+                display("could not build tls configuration: {}", reason),
+            }
+
+            /// If an in-cluster config is asked for outside of a pod, or the
+            /// service account of the pod can not be read, this error will be
+            /// returned
+            NotInCluster(reason: String) {
+                description("not running in a cluster"),
+                display("could not load in-cluster config: {}", reason),
+            }
+
+            /// If the tokens of an `oidc` auth-provider could not be refreshed
+            /// this error will be returned
+            OidcRefresh(reason: String) {
+                description("oidc token refresh failed"),
+                display("oidc token refresh failed: {}", reason),
+            }
         }
     }
 }
@@ -73,81 +230,388 @@ pub mod errors {
 use cluster::Cluster;
 use context::Context;
 use errors::*;
+use extension::Extensions;
+use index::Index;
+use lock::{write_atomic, FileLock};
+use origin::Origins;
+use preferences::Preferences;
 use serde_yaml::Mapping;
+use std::collections::HashMap;
 use std::env;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::mem;
+use std::path::{Path, PathBuf};
 use user::User;
 
 /// The main struct that holds the entire config map.
 /// See the methods on this struct for ways to parse a config.
+///
+/// New configs are built with [`Config::new`](#method.new) or
+/// `Config::default()`, as some of the fields are private.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
-#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    /// Will typically be "v1", generally not needed
+    #[serde(rename = "apiVersion", skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+
+    /// The clusters as defined by the "clusters" key
+    pub clusters: Vec<Cluster>,
+
+    /// The contexts as defined by the "contexts" key
+    pub contexts: Vec<Context>,
+
     /// The name of the current active context.
     /// The actual context can be retrieved by finding the context in the
     /// context set based on this name.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub current_context: Option<String>,
 
-    /// Preferences provided in the config.yml file.
-    pub preferences: Option<Mapping>,
+    /// Extensions that other tools have stored on the config
+    #[serde(default, skip_serializing_if = "Extensions::is_empty")]
+    pub extensions: Extensions,
 
-    /// The clusters as defined by the "clusters" key
-    pub clusters: Vec<Cluster>,
+    /// Will typically be "Config", generally not needed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
 
-    /// The contexts as defined by the "contexts" key
-    pub contexts: Vec<Context>,
+    /// Preferences provided in the config.yml file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferences: Option<Preferences>,
 
     /// The users as defined by the "users" key
     pub users: Vec<User>,
 
-    /// Will typically be "v1", generally not needed
-    #[serde(rename = "apiVersion")]
-    pub api_version: Option<String>,
+    /// Any top level keys that are not known to this crate. These are kept so
+    /// that no data is lost when the config is written back.
+    #[serde(flatten)]
+    pub extra: Mapping,
 
-    /// Will typically be "Config", generally not needed
-    pub kind: Option<String>,
+    /// The file this config was loaded from
+    #[serde(skip)]
+    source: Option<PathBuf>,
+
+    /// Whether the relative paths in the config were resolved when loading
+    #[serde(skip)]
+    resolved: bool,
+
+    /// The paths that were changed when resolving them, with the way they
+    /// were written in the file
+    #[serde(skip)]
+    original_paths: HashMap<PathBuf, PathBuf>,
+
+    /// The positions of the entries by name, for fast lookups
+    #[serde(skip)]
+    index: Index,
+
+    /// The files the config and each of its entries were loaded from
+    #[serde(skip)]
+    origins: Origins,
 }
 
 impl Config {
+    /// Creates an empty config with `apiVersion` and `kind` set the way
+    /// `kubectl` writes them. `Config::default()` leaves them unset.
+    pub fn new() -> Config {
+        Config {
+            api_version: Some("v1".to_string()),
+            kind: Some("Config".to_string()),
+            ..Default::default()
+        }
+    }
+
     /// Fetches the current config based on the user's configured environment.
     ///
     /// This includes `$KUBECONFIG` when set, or simply `$HOME/.kube/config`
-    /// otherwise.
-    ///
-    /// TODO: Support multiple kubeconfig files in the KUBECONFIG env var
-    /// separated by colons, i.e. `KUBECONFIG=file1:file2`. Merge the result.
+    /// otherwise. `$KUBECONFIG` may contain several paths separated by the
+    /// platform's path separator (`:` on unix), in which case the files are
+    /// merged as described in [`Config::load_all`](#method.load_all).
     pub fn load_default() -> Result<Config> {
-        if let Ok(conf_path) = env::var("KUBECONFIG") {
-            return Config::load(&conf_path);
+        if let Some(conf_paths) = env::var_os("KUBECONFIG") {
+            if !conf_paths.is_empty() {
+                return Config::load_all(env::split_paths(&conf_paths));
+            }
         }
 
         if let Ok(home) = env::var("HOME") {
-            return Config::load(&format!("{}/.kube/config", &home));
+            return Config::load(format!("{}/.kube/config", &home));
         }
 
-        bail!("Neither $KUBECONFIG nor $HOME are defined");
+        bail!(ErrorKind::NoConfigLocation);
+    }
+
+    /// Fetches and merges the configs from the provided paths.
+    ///
+    /// The merge follows the same rules as `kubectl`:
+    ///
+    /// - The first file to define a cluster, context or user with a given name
+    ///   wins, later entries with the same name are ignored
+    /// - The first file to set a non-empty `current-context` wins
+    /// - Paths that do not exist are skipped
+    ///
+    /// An error is returned if none of the paths exist.
+    pub fn load_all<I, P>(paths: I) -> Result<Config>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut seen: Vec<PathBuf> = Vec::new();
+        let mut merged: Option<Config> = None;
+
+        for path in paths {
+            let path = path.as_ref();
+            if path.as_os_str().is_empty() || seen.iter().any(|p| p == path) {
+                continue;
+            }
+            seen.push(path.to_path_buf());
+
+            if !path.exists() {
+                continue;
+            }
+
+            let conf = Config::load(path)?;
+            match merged {
+                Some(ref mut merged) => merged.merge(conf),
+                None => merged = Some(conf),
+            }
+        }
+
+        match merged {
+            Some(conf) => Ok(conf),
+            None => {
+                let names: Vec<String> = seen.iter().map(|p| format!("{}", p.display())).collect();
+                bail!(ErrorKind::MissingConfigFile(names.join(", ")))
+            }
+        }
     }
 
     /// Fetches the config from the provided path.
+    ///
+    /// Relative paths to certificates, keys and exec plugins are resolved
+    /// against the directory of the file, and a leading `~` is expanded to the
+    /// home directory, like `kubectl` does. Use
+    /// [`Config::load_raw`](#method.load_raw) to get the paths as they are
+    /// written in the file.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let mut conf = Config::load_raw(&path)?;
+        let dir = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        conf.resolve_paths(&env::current_dir()?.join(dir));
+        Ok(conf)
+    }
+
+    /// Fetches the config from the provided path, keeping all paths in it
+    /// exactly as they are written in the file.
+    pub fn load_raw<P: AsRef<Path>>(path: P) -> Result<Config> {
         let conf = read_to_string(&path)
             .chain_err(|| ErrorKind::MissingConfigFile(format!("{}", path.as_ref().display())))?;
-        let conf = serde_yaml::from_str(&conf)?;
+        let mut conf: Config = serde_yaml::from_str(&conf)?;
+        conf.source = Some(path.as_ref().to_path_buf());
+        conf.reindex();
+        conf.origins = Origins::of_file(path.as_ref(), &conf);
         Ok(conf)
     }
 
+    /// The file this config was loaded from. This is `None` for configs that
+    /// were not loaded from a file, or that were merged from several files.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Writes the config to the provided path as kubeconfig yaml, replacing
+    /// the file if it already exists.
+    ///
+    /// If the config was loaded with [`Config::load`](#method.load), paths
+    /// that were relative or started with `~` in the file are written the way
+    /// they were, as long as they still point to the same file from `path`.
+    /// All other paths are written as they are.
+    ///
+    /// Like `kubectl`, the file is locked by creating `<path>.lock` while it
+    /// is written, waiting up to 5 seconds for other writers to release it.
+    /// The new contents are written to a temporary file that is renamed over
+    /// the old one, which keeps its permissions. New files are created with
+    /// mode `0600`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let _lock = FileLock::acquire(path.as_ref())?;
+        self.save_locked(path.as_ref())
+    }
+
+    /// Writes the config to a file whose lock is already held
+    pub(crate) fn save_locked(&self, path: &Path) -> Result<()> {
+        let conf = match (self.resolved, path.parent()) {
+            (true, Some(dir)) => {
+                let mut conf = self.clone();
+                conf.restore_paths(&env::current_dir()?.join(dir));
+                conf.to_yaml_string()?
+            }
+            _ => self.to_yaml_string()?,
+        };
+        write_atomic(path, &conf)
+    }
+
+    /// Serializes the config to a kubeconfig yaml string, in the block style
+    /// `kubectl` writes, with the keys sorted.
+    pub fn to_yaml_string(&self) -> Result<String> {
+        let lines = yaml::render(&serde_yaml::to_value(self)?, 0)?;
+        Ok(format!("{}\n", lines.join("\n")))
+    }
+
+    /// Checks if the `oidc` auth-provider of the given user has an expired ID
+    /// token, and if so uses the refresh token to get a new one from the
+    /// identity provider. Like `kubectl`, the new tokens are written back to
+    /// the file the user was loaded from, which is locked while it is read
+    /// and written. Nothing else is written, so other changes to the config
+    /// stay in memory. Configs that were not loaded from a file only keep
+    /// the new tokens in memory.
+    ///
+    /// Returns true if the tokens were refreshed.
+    #[cfg(feature = "oidc")]
+    pub fn refresh_oidc_token(&mut self, user_name: &str) -> Result<bool> {
+        let user = match self.users.iter_mut().find(|u| u.name == user_name) {
+            Some(user) => user,
+            None => bail!(ErrorKind::UnknownUser(user_name.to_string())),
+        };
+
+        let oidc = match &mut user.auth_provider {
+            Some(auth_provider::AuthProvider::Oidc(oidc)) => oidc,
+            _ => return Ok(false),
+        };
+
+        if !oidc.id_token_expired() {
+            return Ok(false);
+        }
+
+        oidc.refresh()?;
+        let refreshed = oidc.clone();
+        if let Some(path) = self.user_source(user_name) {
+            self.write_oidc_tokens(path, user_name, &refreshed)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Writes refreshed tokens to the `oidc` auth-provider of the user in the
+    /// given file, leaving the rest of the file as it is
+    #[cfg(feature = "oidc")]
+    fn write_oidc_tokens(
+        &self,
+        path: &Path,
+        user_name: &str,
+        refreshed: &auth_provider::OidcConfig,
+    ) -> Result<()> {
+        let _lock = FileLock::acquire(path)?;
+        let mut file = if self.resolved {
+            Config::load(path)?
+        } else {
+            Config::load_raw(path)?
+        };
+
+        // The user may have been changed or removed by someone else since
+        // the config was loaded
+        let mut user = match file.user(user_name) {
+            Some(user) => user.clone(),
+            None => return Ok(()),
+        };
+        match &mut user.auth_provider {
+            Some(auth_provider::AuthProvider::Oidc(oidc)) => {
+                oidc.id_token = refreshed.id_token.clone();
+                oidc.refresh_token = refreshed.refresh_token.clone();
+            }
+            _ => return Ok(()),
+        }
+        file.replace_user(user);
+        file.save_locked(path)
+    }
+
     /// Gets the currently active context based on the `current-context` key in
     /// the config file.
     pub fn get_current_context(&self) -> Option<&Context> {
-        if let Some(current_context) = &self.current_context {
-            for context in self.contexts.iter() {
-                if &context.name == current_context {
-                    return Some(context);
-                }
+        self.context(self.current_context.as_ref()?)
+    }
+
+    /// Builds a config with only the given context, or the current context
+    /// if none is given, and the cluster and user it refers to, like
+    /// `kubectl config view --minify`. The `current-context` of the result is
+    /// set to that context, and `preferences` and `extensions` are kept.
+    ///
+    /// The result is not tied to the files this config was loaded from, so
+    /// it has to be written with [`Config::save`](#method.save).
+    pub fn minify(&self, context_name: Option<&str>) -> Result<Config> {
+        let context_name = match context_name.or(self.current_context.as_deref()) {
+            Some(name) if !name.is_empty() => name,
+            _ => bail!("current-context must be set to minify the config"),
+        };
+        let context = self
+            .context(context_name)
+            .ok_or_else(|| ErrorKind::UnknownContext(context_name.to_string()))?;
+        let cluster = self
+            .cluster(&context.cluster)
+            .ok_or_else(|| ErrorKind::UnknownCluster(context.cluster.clone()))?;
+        let user = self
+            .user(&context.user)
+            .ok_or_else(|| ErrorKind::UnknownUser(context.user.clone()))?;
+
+        let mut minified = Config {
+            api_version: self.api_version.clone(),
+            clusters: vec![cluster.clone()],
+            contexts: vec![context.clone()],
+            current_context: Some(context_name.to_string()),
+            extensions: self.extensions.clone(),
+            kind: self.kind.clone(),
+            preferences: self.preferences.clone(),
+            users: vec![user.clone()],
+            extra: self.extra.clone(),
+            source: None,
+            resolved: self.resolved,
+            original_paths: self.original_paths.clone(),
+            index: Default::default(),
+            origins: Default::default(),
+        };
+        minified.reindex();
+        Ok(minified)
+    }
+
+    /// Merges `other` into this config. Entries already present in this
+    /// config take precedence over the ones in `other`.
+    fn merge(&mut self, other: Config) {
+        // A merged config can not be written back to a single file
+        self.source = None;
+        let mut origins = mem::take(&mut self.origins);
+        origins.merge(other.origins);
+
+        if self.current_context.as_ref().is_none_or(String::is_empty) {
+            self.current_context = other.current_context;
+        }
+
+        match (&mut self.preferences, other.preferences) {
+            (Some(preferences), Some(other)) => preferences.merge(other),
+            (preferences @ None, other) => *preferences = other,
+            _ => {}
+        }
+
+        self.extensions.merge(other.extensions);
+
+        self.merge_entries(other.clusters, other.contexts, other.users);
+
+        if self.api_version.is_none() {
+            self.api_version = other.api_version;
+        }
+
+        if self.kind.is_none() {
+            self.kind = other.kind;
+        }
+
+        for (key, value) in other.extra {
+            if !self.extra.contains_key(&key) {
+                self.extra.insert(key, value);
             }
         }
 
-        None
+        self.resolved |= other.resolved;
+        for (resolved, original) in other.original_paths {
+            self.original_paths.entry(resolved).or_insert(original);
+        }
+
+        origins.snapshot(self);
+        self.origins = origins;
     }
 }
@@ -0,0 +1,114 @@
+//! Writing config files without racing other writers, using the same
+//! `<file>.lock` file as `kubectl`
+
+use crate::errors::*;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for another writer to release the lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait between attempts to take the lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Tells apart the temporary files of the threads of this process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// An exclusive lock on a config file, held for as long as this value lives.
+///
+/// Like `kubectl`, the lock is taken by creating `<file>.lock`, which fails
+/// while another writer has it, and released by removing it again.
+pub(crate) struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Takes the lock on the given config file, retrying until the lock is
+    /// released or the timeout is reached
+    pub(crate) fn acquire(path: &Path) -> Result<FileLock> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(_) => return Ok(FileLock { path: lock_path }),
+                Err(e) if e.kind() == IoErrorKind::AlreadyExists => {
+                    if started.elapsed() >= LOCK_TIMEOUT {
+                        bail!(ErrorKind::ConfigLocked(format!("{}", path.display())));
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(e)
+                        .chain_err(|| format!("could not lock config file: '{}'", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Replaces the contents of a file by writing a temporary file next to it and
+/// renaming it over the original, so readers never see a partly written file.
+///
+/// The file keeps its permissions, and new files are only readable by the
+/// owner. If `path` is a symlink the file it points to is replaced.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(_) => path.to_path_buf(),
+    };
+    let permissions = fs::metadata(&path).ok().map(|m| m.permissions());
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = write_new(&temp_path, contents, permissions)
+        .and_then(|_| fs::rename(&temp_path, &path).map_err(Error::from));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.chain_err(|| format!("could not write config file: '{}'", path.display()))
+}
+
+fn write_new(path: &Path, contents: &str, permissions: Option<fs::Permissions>) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
+    }
+    file.sync_all()?;
+    Ok(())
+}
@@ -0,0 +1,246 @@
+//! Remembering which file each entry of a merged config came from, so that
+//! changes can be written back to it
+
+use crate::errors::*;
+use crate::extension::Extensions;
+use crate::lock::FileLock;
+use crate::preferences::Preferences;
+use crate::Config;
+use serde_yaml::Mapping;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The files a config was loaded from, and the file each cluster, context and
+/// user came from, by name. The top level values are kept as they were loaded
+/// so that they are only written back when they changed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Origins {
+    files: Vec<PathBuf>,
+    clusters: HashMap<String, usize>,
+    contexts: HashMap<String, usize>,
+    users: HashMap<String, usize>,
+    /// The renamed contexts, from their old name to their new name and file,
+    /// which are renamed in place in their file
+    renamed_contexts: HashMap<String, (String, usize)>,
+    current_context: Option<String>,
+    preferences: Option<Preferences>,
+    extensions: Extensions,
+    extra: Mapping,
+}
+
+impl Origins {
+    /// The origins of a config loaded from a single file
+    pub(crate) fn of_file(path: &Path, config: &Config) -> Origins {
+        let mut origins = Origins {
+            files: vec![path.to_path_buf()],
+            ..Default::default()
+        };
+        origins.claim_new(config, 0);
+        origins.snapshot(config);
+        origins
+    }
+
+    /// Adds the files and entries of `other`, which is merged after this
+    /// config. Entries that already have a file keep it.
+    pub(crate) fn merge(&mut self, other: Origins) {
+        let offset = self.files.len();
+        self.files.extend(other.files);
+        for (names, other_names) in [
+            (&mut self.clusters, other.clusters),
+            (&mut self.contexts, other.contexts),
+            (&mut self.users, other.users),
+        ] {
+            for (name, file) in other_names {
+                names.entry(name).or_insert(file + offset);
+            }
+        }
+    }
+
+    /// Keeps the top level values of the config to tell later whether they
+    /// changed
+    pub(crate) fn snapshot(&mut self, config: &Config) {
+        self.current_context = config.current_context.clone();
+        self.preferences = config.preferences.clone();
+        self.extensions = config.extensions.clone();
+        self.extra = config.extra.clone();
+    }
+
+    /// Moves the file of a renamed context to its new name
+    pub(crate) fn rename_context(&mut self, from: &str, to: &str) {
+        if let Some(file) = self.contexts.remove(from) {
+            // A context renamed again keeps the name it has in the file
+            let original = self
+                .renamed_contexts
+                .iter()
+                .find(|(_, (name, _))| name == from)
+                .map(|(original, _)| original.clone())
+                .unwrap_or_else(|| from.to_string());
+            self.renamed_contexts
+                .insert(original, (to.to_string(), file));
+            self.contexts.insert(to.to_string(), file);
+        }
+    }
+
+    /// Assigns the entries that have no file yet to the given file
+    fn claim_new(&mut self, config: &Config, file: usize) {
+        for cluster in &config.clusters {
+            self.clusters.entry(cluster.name.clone()).or_insert(file);
+        }
+        for context in &config.contexts {
+            self.contexts.entry(context.name.clone()).or_insert(file);
+        }
+        for user in &config.users {
+            self.users.entry(user.name.clone()).or_insert(file);
+        }
+    }
+
+    /// Forgets the entries that are no longer in the config
+    fn forget_removed(&mut self, config: &Config) {
+        self.clusters
+            .retain(|name, _| config.cluster(name).is_some());
+        self.contexts
+            .retain(|name, _| config.context(name).is_some());
+        self.users.retain(|name, _| config.user(name).is_some());
+        self.renamed_contexts.clear();
+    }
+}
+
+impl Config {
+    /// The files this config was loaded from, in the order they were merged
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.origins.files
+    }
+
+    /// The file the cluster with the given name was loaded from
+    pub fn cluster_source(&self, name: &str) -> Option<&Path> {
+        self.file_of(self.origins.clusters.get(name))
+    }
+
+    /// The file the context with the given name was loaded from
+    pub fn context_source(&self, name: &str) -> Option<&Path> {
+        self.file_of(self.origins.contexts.get(name))
+    }
+
+    /// The file the user with the given name was loaded from
+    pub fn user_source(&self, name: &str) -> Option<&Path> {
+        self.file_of(self.origins.users.get(name))
+    }
+
+    /// Writes the changes made to this config back to the files it was loaded
+    /// from, the way `kubectl` does:
+    ///
+    /// - Changed and removed entries are written to the file they came from
+    /// - New entries are written to the first file
+    /// - `current-context`, `preferences`, `extensions` and the unknown top
+    ///   level keys are written to the first file when they changed
+    ///
+    /// Each file is locked while it is read and written again, see
+    /// [`Config::save`](#method.save). Files without changes are not written,
+    /// so they stay byte for byte the same. Entries in a file that lost to an
+    /// entry with the same name in an earlier file are left alone.
+    pub fn persist(&mut self) -> Result<()> {
+        if self.origins.files.is_empty() {
+            bail!("the config was not loaded from a file");
+        }
+
+        for (file, path) in self.origins.files.iter().enumerate() {
+            // Hold the lock from reading the file until it is written, so
+            // changes made by other writers in between are not lost
+            let _lock = FileLock::acquire(path)?;
+            let original = if self.resolved {
+                Config::load(path)?
+            } else {
+                Config::load_raw(path)?
+            };
+            let changed = self.apply_to(original.clone(), file);
+            if changed.to_yaml_string()? != original.to_yaml_string()? {
+                changed.save_locked(path)?;
+            }
+        }
+
+        let mut origins = self.origins.clone();
+        origins.forget_removed(self);
+        origins.claim_new(self, 0);
+        origins.snapshot(self);
+        self.origins = origins;
+
+        Ok(())
+    }
+
+    /// Applies the changes that belong in the given file to the config loaded
+    /// from it
+    fn apply_to(&self, mut target: Config, file: usize) -> Config {
+        let origins = &self.origins;
+        let belongs = |names: &HashMap<String, usize>, name: &str| match names.get(name) {
+            Some(origin) => *origin == file,
+            None => file == 0,
+        };
+
+        // Renames go first, so the renamed context keeps its place in the
+        // file, and a context that takes its old name is not mixed up with it
+        for (from, (to, origin)) in &origins.renamed_contexts {
+            if *origin != file {
+                continue;
+            }
+            if target.context(to).is_none() {
+                let _ = target.rename_context(from, to);
+            } else if origins.contexts.get(from) != Some(&file) {
+                target.remove_context(from);
+            }
+        }
+
+        for (name, origin) in &origins.clusters {
+            if *origin == file && self.cluster(name).is_none() {
+                target.remove_cluster(name);
+            }
+        }
+        for (name, origin) in &origins.contexts {
+            if *origin == file && self.context(name).is_none() {
+                target.remove_context(name);
+            }
+        }
+        for (name, origin) in &origins.users {
+            if *origin == file && self.user(name).is_none() {
+                target.remove_user(name);
+            }
+        }
+
+        for cluster in &self.clusters {
+            if belongs(&origins.clusters, &cluster.name) {
+                target.replace_cluster(cluster.clone());
+            }
+        }
+        for context in &self.contexts {
+            if belongs(&origins.contexts, &context.name) {
+                target.replace_context(context.clone());
+            }
+        }
+        for user in &self.users {
+            if belongs(&origins.users, &user.name) {
+                target.replace_user(user.clone());
+            }
+        }
+
+        if file == 0 {
+            if self.current_context != origins.current_context {
+                target.current_context = self.current_context.clone();
+            }
+            if self.preferences != origins.preferences {
+                target.preferences = self.preferences.clone();
+            }
+            if self.extensions != origins.extensions {
+                target.extensions = self.extensions.clone();
+            }
+            if self.extra != origins.extra {
+                target.extra = self.extra.clone();
+            }
+        }
+
+        target
+    }
+
+    fn file_of(&self, file: Option<&usize>) -> Option<&Path> {
+        file.and_then(|file| self.origins.files.get(*file))
+            .map(PathBuf::as_path)
+    }
+}
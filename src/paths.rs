@@ -0,0 +1,98 @@
+//! Resolving the file paths in a config against the directory of the file it
+//! was loaded from
+
+use crate::auth_provider::AuthProvider;
+use crate::Config;
+use std::collections::HashMap;
+use std::env;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+impl Config {
+    /// Makes all relative paths absolute by resolving them against `dir`,
+    /// expanding a leading `~` to the home directory.
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        let mut original_paths = HashMap::new();
+        self.for_each_path(|path| {
+            let resolved = resolve(path, dir);
+            if resolved != *path {
+                original_paths.insert(resolved.clone(), mem::replace(path, resolved));
+            }
+        });
+        self.original_paths = original_paths;
+        self.resolved = true;
+    }
+
+    /// Puts back the paths changed by `resolve_paths` the way they were
+    /// written, if they still point to the same file when resolved against
+    /// `dir`.
+    pub(crate) fn restore_paths(&mut self, dir: &Path) {
+        let original_paths = mem::take(&mut self.original_paths);
+        self.for_each_path(|path| {
+            if let Some(original) = original_paths.get(path) {
+                if resolve(original, dir) == *path {
+                    *path = original.clone();
+                }
+            }
+        });
+        self.original_paths = original_paths;
+    }
+
+    fn for_each_path<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut PathBuf),
+    {
+        for cluster in self.clusters.iter_mut() {
+            if let Some(path) = &mut cluster.certificate_authority {
+                f(path);
+            }
+        }
+
+        for user in self.users.iter_mut() {
+            if let Some(path) = &mut user.token_file {
+                f(path);
+            }
+            if let Some(path) = &mut user.client_certificate {
+                f(path);
+            }
+            if let Some(path) = &mut user.client_key {
+                f(path);
+            }
+            if let Some(AuthProvider::Oidc(oidc)) = &mut user.auth_provider {
+                if let Some(path) = &mut oidc.idp_certificate_authority {
+                    f(path);
+                }
+            }
+            // Like kubectl, only commands given as a path are resolved. Bare
+            // names are looked up in $PATH.
+            if let Some(exec) = &mut user.exec {
+                let mut command = PathBuf::from(&exec.command);
+                if command.components().count() > 1 {
+                    f(&mut command);
+                    // Keep the command recognizable as a path
+                    if command.components().count() == 1 {
+                        command = Path::new(".").join(command);
+                    }
+                    exec.command = command.to_string_lossy().into_owned();
+                }
+            }
+        }
+    }
+}
+
+/// Resolves a path the way `kubectl` does, see `Config::resolve_paths`
+fn resolve(path: &Path, dir: &Path) -> PathBuf {
+    let path = expand_home(path).unwrap_or_else(|| path.to_path_buf());
+    if path.is_relative() {
+        dir.join(path)
+    } else {
+        path
+    }
+}
+
+/// Expands a leading `~` in the path to the user's home directory
+fn expand_home(path: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix("~").ok()?;
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(rest))
+}
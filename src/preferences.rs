@@ -0,0 +1,98 @@
+//! The module holding the `Preferences` struct
+
+use crate::errors;
+use crate::extension::Extensions;
+use crate::get::{get_bool, get_optional, get_remainder, Kind};
+use crate::set::{set_bool, set_extra, set_serialized};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+
+/// The `preferences` of a config.
+///
+/// Keys other than `colors` and `extensions` are kept in `extra`, and can be
+/// decoded with [`get`](#method.get).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preferences {
+    /// Whether kubectl should use colors in its output
+    pub colors: Option<bool>,
+
+    /// Extensions that other tools have stored in the preferences
+    pub extensions: Extensions,
+
+    /// Any keys in the `preferences` mapping that are not known to this crate.
+    /// These are kept so that no data is lost when the config is written back.
+    pub extra: Mapping,
+}
+
+/// The keys of the `preferences` mapping that are parsed into typed fields
+const PREFERENCES_KEYS: &[(&str, Kind)] = &[("colors", Kind::Bool), ("extensions", Kind::Other)];
+
+impl Preferences {
+    /// Decodes the custom preference with the given key, or returns `None`
+    /// when it is not set. An error is returned when it does not decode as a
+    /// `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> errors::Result<Option<T>> {
+        match self.get_value(key) {
+            Some(value) => Ok(Some(serde_yaml::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the raw value of the custom preference with the given key
+    pub fn get_value(&self, key: &str) -> Option<&Value> {
+        self.extra.get(&Value::String(key.to_string()))
+    }
+
+    /// Sets the custom preference with the given key
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> errors::Result<()> {
+        self.extra
+            .insert(Value::String(key.to_string()), serde_yaml::to_value(value)?);
+        Ok(())
+    }
+
+    /// Fills in the preferences that are only set in `other`
+    pub(crate) fn merge(&mut self, other: Preferences) {
+        if self.colors.is_none() {
+            self.colors = other.colors;
+        }
+        self.extensions.merge(other.extensions);
+        for (key, value) in other.extra {
+            if !self.extra.contains_key(&key) {
+                self.extra.insert(key, value);
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Preferences {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let map: Mapping = Deserialize::deserialize(d)?;
+
+        Ok(Preferences {
+            colors: get_bool::<D::Error>(&map, "colors").ok(),
+            extensions: get_optional(&map, "extensions")?.unwrap_or_default(),
+            extra: get_remainder(&map, PREFERENCES_KEYS),
+        })
+    }
+}
+
+impl Serialize for Preferences {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = Mapping::new();
+        if let Some(colors) = self.colors {
+            set_bool(&mut map, "colors", colors);
+        }
+        if !self.extensions.is_empty() {
+            set_serialized::<_, S::Error>(&mut map, "extensions", &self.extensions)?;
+        }
+        set_extra(&mut map, &self.extra);
+        map.serialize(s)
+    }
+}
@@ -0,0 +1,50 @@
+use serde::ser::{Error, Serialize};
+use serde_yaml::Mapping;
+use serde_yaml::Value;
+use std::path::Path;
+
+pub fn set_string(map: &mut Mapping, key: &str, value: &str) {
+    map.insert(
+        Value::String(String::from(key)),
+        Value::String(String::from(value)),
+    );
+}
+
+pub fn set_bool(map: &mut Mapping, key: &str, value: bool) {
+    map.insert(Value::String(String::from(key)), Value::Bool(value));
+}
+
+pub fn set_mapping(map: &mut Mapping, key: &str, value: Mapping) {
+    map.insert(Value::String(String::from(key)), Value::Mapping(value));
+}
+
+pub fn set_path<E>(map: &mut Mapping, key: &str, value: &Path) -> Result<(), E>
+where
+    E: Error,
+{
+    let value = value
+        .to_str()
+        .ok_or_else(|| E::custom("Path could not be represented as a string"))?;
+    set_string(map, key, value);
+    Ok(())
+}
+
+pub fn set_serialized<T, E>(map: &mut Mapping, key: &str, value: &T) -> Result<(), E>
+where
+    T: Serialize,
+    E: Error,
+{
+    let value = serde_yaml::to_value(value).map_err(E::custom)?;
+    map.insert(Value::String(String::from(key)), value);
+    Ok(())
+}
+
+/// Adds the keys of `extra` that are not set yet, so that the typed fields win
+/// over values that were kept because they could not be parsed
+pub fn set_extra(map: &mut Mapping, extra: &Mapping) {
+    for (key, value) in extra {
+        if !map.contains_key(key) {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+}
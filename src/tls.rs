@@ -0,0 +1,355 @@
+//! Building TLS client configurations from a context
+
+#[cfg(any(feature = "reqwest", feature = "tower"))]
+pub(crate) use self::rustls_config::client_config;
+
+#[cfg(feature = "rustls")]
+mod rustls_config {
+    use crate::cluster::Cluster;
+    use crate::errors::*;
+    use crate::{Config, Context};
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+    use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+    use std::convert::TryFrom;
+    use std::sync::Arc;
+
+    impl Context {
+        /// Builds a `rustls::ClientConfig` for connecting to the cluster of
+        /// this context.
+        ///
+        /// The certificate authority of the cluster is trusted, or the roots
+        /// of the platform when the cluster has none. The client certificate
+        /// and key of the user are used as the client identity when set. When
+        /// the cluster has `insecure-skip-tls-verify` set, the certificate of
+        /// the server is not verified at all.
+        ///
+        /// When the cluster has `tls-server-name` set, the certificate of the
+        /// server is verified against that name instead of the host the
+        /// connection is made to. `rustls` sends the name given when
+        /// connecting in the SNI extension, so connect with `tls-server-name`
+        /// as the server name to send it like client-go does.
+        /// [`Context::https_connector`](#method.https_connector) and
+        /// [`Config::http_client`](../struct.Config.html#method.http_client)
+        /// do this.
+        pub fn rustls_config(&self, config: &Config) -> Result<ClientConfig> {
+            let cluster = self
+                .get_cluster(config)
+                .ok_or_else(|| ErrorKind::UnknownCluster(self.cluster.clone()))?;
+            let user = self
+                .get_user(config)
+                .ok_or_else(|| ErrorKind::UnknownUser(self.user.clone()))?;
+
+            let identity = match (user.client_cert_pem()?, user.client_key_pem()?) {
+                (Some(cert), Some(key)) => Some((cert, key)),
+                _ => None,
+            };
+            client_config(&cluster, identity)
+        }
+    }
+
+    /// Builds the `rustls::ClientConfig` for the cluster, using the given PEM
+    /// encoded certificate and key as the client identity
+    pub(crate) fn client_config(
+        cluster: &Cluster,
+        identity: Option<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<ClientConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .chain_err(|| ErrorKind::Tls("unsupported protocol versions".to_string()))?;
+
+        let builder = if cluster.insecure_skip_tls_verify {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+        } else {
+            let mut roots = RootCertStore::empty();
+            match cluster.ca_pem()? {
+                Some(ca) => {
+                    for cert in CertificateDer::pem_slice_iter(&ca) {
+                        let cert = cert.chain_err(|| {
+                            ErrorKind::Tls("invalid certificate authority".to_string())
+                        })?;
+                        roots.add(cert).chain_err(|| {
+                            ErrorKind::Tls("invalid certificate authority".to_string())
+                        })?;
+                    }
+                }
+                None => {
+                    let native = rustls_native_certs::load_native_certs();
+                    roots.add_parsable_certificates(native.certs);
+                }
+            }
+            match &cluster.tls_server_name {
+                Some(tls_server_name) => {
+                    let name = ServerName::try_from(tls_server_name.clone()).chain_err(|| {
+                        ErrorKind::Tls(format!("invalid tls-server-name '{}'", tls_server_name))
+                    })?;
+                    let verifier = WebPkiServerVerifier::builder_with_provider(
+                        Arc::new(roots),
+                        provider.clone(),
+                    )
+                    .build()
+                    .chain_err(|| ErrorKind::Tls("invalid certificate authority".to_string()))?;
+                    builder
+                        .dangerous()
+                        .with_custom_certificate_verifier(Arc::new(FixedServerName {
+                            verifier,
+                            name,
+                        }))
+                }
+                None => builder.with_root_certificates(roots),
+            }
+        };
+
+        match identity {
+            Some((cert, key)) => {
+                let certs = CertificateDer::pem_slice_iter(&cert)
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .chain_err(|| ErrorKind::Tls("invalid client certificate".to_string()))?;
+                let key = PrivateKeyDer::from_pem_slice(&key)
+                    .chain_err(|| ErrorKind::Tls("invalid client key".to_string()))?;
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .chain_err(|| ErrorKind::Tls("invalid client identity".to_string()))
+            }
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+
+    /// Accepts any server certificate, used for `insecure-skip-tls-verify`.
+    /// Handshake signatures are still checked so the connection is at least
+    /// bound to the certificate the server presented.
+    #[derive(Debug)]
+    struct NoVerification(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for NoVerification {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// Verifies the server certificate against a fixed name, used for
+    /// `tls-server-name`
+    #[derive(Debug)]
+    struct FixedServerName {
+        verifier: Arc<WebPkiServerVerifier>,
+        name: ServerName<'static>,
+    }
+
+    impl ServerCertVerifier for FixedServerName {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+            self.verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                &self.name,
+                ocsp_response,
+                now,
+            )
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            self.verifier.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            self.verifier.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.verifier.supported_verify_schemes()
+        }
+    }
+}
+
+#[cfg(feature = "native-tls")]
+mod native_tls_connector {
+    use crate::errors::*;
+    use crate::{Config, Context};
+    use native_tls::{Certificate, Identity, TlsConnector};
+    use pem::Pem;
+    use yasna::models::ObjectIdentifier;
+    use yasna::Tag;
+
+    impl Context {
+        /// Builds a `native_tls::TlsConnector` for connecting to the cluster
+        /// of this context.
+        ///
+        /// The certificate authority of the cluster replaces the built in
+        /// roots when set. The client certificate and key of the user are used
+        /// as the client identity when set. When the cluster has
+        /// `insecure-skip-tls-verify` set, neither the certificate nor the
+        /// hostname of the server are verified.
+        ///
+        /// The connector can not override the name the certificate is verified
+        /// against, so when the cluster has `tls-server-name` set it should be
+        /// given as the domain when connecting.
+        pub fn native_tls_connector(&self, config: &Config) -> Result<TlsConnector> {
+            let cluster = self
+                .get_cluster(config)
+                .ok_or_else(|| ErrorKind::UnknownCluster(self.cluster.clone()))?;
+            let user = self
+                .get_user(config)
+                .ok_or_else(|| ErrorKind::UnknownUser(self.user.clone()))?;
+
+            let mut builder = TlsConnector::builder();
+            if cluster.insecure_skip_tls_verify {
+                builder
+                    .danger_accept_invalid_certs(true)
+                    .danger_accept_invalid_hostnames(true);
+            } else if let Some(ca) = cluster.ca_pem()? {
+                builder.disable_built_in_roots(true);
+                for block in pem_blocks(&ca)? {
+                    let cert = Certificate::from_der(block.contents()).chain_err(|| {
+                        ErrorKind::Tls("invalid certificate authority".to_string())
+                    })?;
+                    builder.add_root_certificate(cert);
+                }
+            }
+
+            if let (Some(cert), Some(key)) = (user.client_cert_pem()?, user.client_key_pem()?) {
+                let key = pkcs8_pem(&key)?;
+                let identity = Identity::from_pkcs8(&cert, key.as_bytes())
+                    .chain_err(|| ErrorKind::Tls("invalid client identity".to_string()))?;
+                builder.identity(identity);
+            }
+
+            builder
+                .build()
+                .chain_err(|| ErrorKind::Tls("could not build connector".to_string()))
+        }
+    }
+
+    /// The object identifier of RSA keys
+    const RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 1];
+
+    /// The object identifier of EC public keys, the curve follows it
+    const EC_PUBLIC_KEY: &[u64] = &[1, 2, 840, 10045, 2, 1];
+
+    /// Converts a PEM encoded private key to PKCS#8, which is the only format
+    /// `native_tls::Identity` accepts. Kubeconfigs typically hold PKCS#1 RSA
+    /// keys or SEC1 EC keys.
+    fn pkcs8_pem(key: &[u8]) -> Result<String> {
+        let invalid = || ErrorKind::Tls("invalid client key".to_string());
+        let key = pem_blocks(key)?
+            .into_iter()
+            .find(|block| block.tag().ends_with("PRIVATE KEY"))
+            .ok_or_else(invalid)?;
+
+        let der = key.contents();
+        let pkcs8 = match key.tag() {
+            "PRIVATE KEY" => der.to_vec(),
+            "RSA PRIVATE KEY" => private_key_info(RSA_ENCRYPTION, None, der),
+            "EC PRIVATE KEY" => {
+                // The curve is given in the explicit [0] parameters of the key
+                let curve = yasna::parse_der(der, |reader| {
+                    reader.read_sequence(|reader| {
+                        reader.next().read_u8()?;
+                        reader.next().read_bytes()?;
+                        let curve = reader.read_optional(|reader| {
+                            reader.read_tagged(Tag::context(0), |reader| reader.read_oid())
+                        })?;
+                        reader.read_optional(|reader| {
+                            reader.read_tagged(Tag::context(1), |reader| reader.read_bitvec_bytes())
+                        })?;
+                        Ok(curve)
+                    })
+                })
+                .chain_err(invalid)?
+                .ok_or_else(invalid)?;
+                private_key_info(EC_PUBLIC_KEY, Some(&curve), der)
+            }
+            _ => return Err(invalid().into()),
+        };
+
+        Ok(pem::encode(&Pem::new("PRIVATE KEY", pkcs8)))
+    }
+
+    /// Wraps a private key of the given algorithm in a PKCS#8 structure
+    fn private_key_info(
+        algorithm: &[u64],
+        parameter: Option<&ObjectIdentifier>,
+        key: &[u8],
+    ) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_u8(0);
+                writer.next().write_sequence(|writer| {
+                    writer
+                        .next()
+                        .write_oid(&ObjectIdentifier::from_slice(algorithm));
+                    match parameter {
+                        Some(parameter) => writer.next().write_oid(parameter),
+                        None => writer.next().write_null(),
+                    }
+                });
+                writer.next().write_bytes(key);
+            })
+        })
+    }
+
+    /// Gets the PEM blocks in the data
+    fn pem_blocks(data: &[u8]) -> Result<Vec<Pem>> {
+        pem::parse_many(data).chain_err(|| ErrorKind::Tls("invalid PEM data".to_string()))
+    }
+}
@@ -0,0 +1,120 @@
+//! The module holding the `TokenSource` struct
+
+use crate::errors::*;
+use std::fs::{metadata, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a token read from a file is used before the file is read again,
+/// the same as client-go
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the bearer token of a user comes from. Tokens read from a file are
+/// read again when the file changes, or when the interval has passed since it
+/// was last read, so that rotated tokens like the projected service account
+/// tokens of a pod are picked up.
+///
+/// Clones share the token that was last read.
+#[derive(Debug, Clone)]
+pub struct TokenSource {
+    source: Source,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Fixed(String),
+    File {
+        path: PathBuf,
+        interval: Duration,
+        state: Arc<Mutex<Option<FileState>>>,
+    },
+}
+
+#[derive(Debug)]
+struct FileState {
+    token: String,
+    modified: Option<SystemTime>,
+    read_at: Instant,
+}
+
+impl TokenSource {
+    /// A token source that always gives the same token
+    pub fn fixed<S: Into<String>>(token: S) -> Self {
+        TokenSource {
+            source: Source::Fixed(token.into()),
+        }
+    }
+
+    /// A token source reading the token from the given file
+    pub fn file<P: Into<PathBuf>>(path: P) -> Self {
+        TokenSource {
+            source: Source::File {
+                path: path.into(),
+                interval: DEFAULT_INTERVAL,
+                state: Arc::new(Mutex::new(None)),
+            },
+        }
+    }
+
+    /// Sets how long a token read from the file is used before the file is
+    /// read again, even if it does not look changed. Defaults to one minute.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        if let Source::File { interval: i, .. } = &mut self.source {
+            *i = interval;
+        }
+        self
+    }
+
+    /// The file the token is read from, if any
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::Fixed(_) => None,
+            Source::File { path, .. } => Some(path),
+        }
+    }
+
+    /// Gets the current token, reading the file again if it changed or the
+    /// interval has passed.
+    ///
+    /// If the file can not be read again the last token is returned, so a
+    /// file that is briefly missing while it is replaced does not fail
+    /// requests. An error is only returned if the file was never read.
+    pub fn current_token(&self) -> Result<String> {
+        let (path, interval, state) = match &self.source {
+            Source::Fixed(token) => return Ok(token.clone()),
+            Source::File {
+                path,
+                interval,
+                state,
+            } => (path, *interval, state),
+        };
+
+        let mut state = state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let modified = metadata(path).and_then(|m| m.modified()).ok();
+        if let Some(current) = &*state {
+            if current.read_at.elapsed() < interval && current.modified == modified {
+                return Ok(current.token.clone());
+            }
+        }
+
+        match read_to_string(path) {
+            Ok(token) => {
+                let token = token.trim().to_string();
+                *state = Some(FileState {
+                    token: token.clone(),
+                    modified,
+                    read_at: Instant::now(),
+                });
+                Ok(token)
+            }
+            Err(e) => match &*state {
+                Some(current) => Ok(current.token.clone()),
+                None => Err(e)
+                    .chain_err(|| ErrorKind::UnreadableFile(path.to_string_lossy().into_owned())),
+            },
+        }
+    }
+}
@@ -1,8 +1,15 @@
 //! The module holding the `User` struct
 
-use crate::get::{get_mapping, get_string};
-use serde::{Deserialize, Deserializer};
+use crate::auth_provider::AuthProvider;
+use crate::data::read_data_or_file;
+use crate::exec::ExecConfig;
+use crate::extension::Extensions;
+use crate::get::{get_mapping, get_optional, get_remainder, get_string, Kind};
+use crate::set::{set_extra, set_mapping, set_path, set_serialized, set_string};
+use crate::token::TokenSource;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Mapping;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// A user represents a user that can be used to log in to one of the clusters
@@ -12,11 +19,16 @@ use std::path::PathBuf;
 /// Note: The user struct is flattened when compared to its representation in
 /// the yaml file. There is no `user` mapping, the values of the `user`
 /// mapping are directly accessible on the `User` struct.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct User {
     /// The name given to this user by the user
     pub name: String,
     pub token: Option<String>,
+
+    /// A `PathBuf` representing a file holding the bearer token of this user.
+    /// The file is read when the token is needed, so it can be rotated.
+    pub token_file: Option<PathBuf>,
+
     pub username: Option<String>,
     pub password: Option<String>,
 
@@ -35,6 +47,161 @@ pub struct User {
     /// A string representing the client key associated with this
     /// user. This is a base64 encoded string containing the CA data.
     pub client_key_data: Option<String>,
+
+    /// An exec credential plugin that provides the credentials of this user
+    pub exec: Option<ExecConfig>,
+
+    /// A legacy auth-provider that provides the credentials of this user
+    pub auth_provider: Option<AuthProvider>,
+
+    /// The user to impersonate, given by the `as` key
+    pub impersonate: Option<String>,
+
+    /// The uid of the user to impersonate, given by the `as-uid` key
+    pub impersonate_uid: Option<String>,
+
+    /// The groups to impersonate, given by the `as-groups` key
+    pub impersonate_groups: Option<Vec<String>>,
+
+    /// Extra information about the user to impersonate, given by the
+    /// `as-user-extra` key
+    pub impersonate_user_extra: Option<BTreeMap<String, Vec<String>>>,
+
+    /// Extensions that other tools have stored on this user
+    pub extensions: Extensions,
+
+    /// Any keys in the `user` mapping that are not known to this crate.
+    /// These are kept so that no data is lost when the config is written back.
+    pub extra: Mapping,
+
+    /// Any keys next to `name` and `user` that are not known to this crate,
+    /// kept for the same reason as `extra`
+    pub entry_extra: Mapping,
+}
+
+/// The keys of the `user` mapping that are parsed into typed fields
+const USER_KEYS: &[(&str, Kind)] = &[
+    ("token", Kind::String),
+    ("tokenFile", Kind::String),
+    ("username", Kind::String),
+    ("password", Kind::String),
+    ("client-certificate", Kind::String),
+    ("client-certificate-data", Kind::String),
+    ("client-key", Kind::String),
+    ("client-key-data", Kind::String),
+    ("exec", Kind::Other),
+    ("auth-provider", Kind::Other),
+    ("as", Kind::String),
+    ("as-uid", Kind::String),
+    ("as-groups", Kind::Other),
+    ("as-user-extra", Kind::Other),
+    ("extensions", Kind::Other),
+];
+
+/// Who a user acts as when talking to the cluster, as set by the `as`,
+/// `as-uid`, `as-groups` and `as-user-extra` keys of the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Impersonation {
+    /// The user to impersonate
+    pub user: String,
+
+    /// The uid of the user to impersonate
+    pub uid: Option<String>,
+
+    /// The groups to impersonate
+    pub groups: Vec<String>,
+
+    /// Extra information about the user to impersonate
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+impl Impersonation {
+    /// Gets the `Impersonate-*` HTTP headers to send to the cluster, as name
+    /// and value pairs. Headers that appear more than once, like
+    /// `Impersonate-Group`, are given once per value.
+    ///
+    /// The keys of `extra` are percent-encoded where they contain characters
+    /// that are not allowed in a header name, like the Kubernetes API server
+    /// expects.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![("Impersonate-User".to_string(), self.user.clone())];
+        if let Some(uid) = &self.uid {
+            headers.push(("Impersonate-Uid".to_string(), uid.clone()));
+        }
+        for group in &self.groups {
+            headers.push(("Impersonate-Group".to_string(), group.clone()));
+        }
+        for (key, values) in &self.extra {
+            let name = format!("Impersonate-Extra-{}", escape_header_name(key));
+            for value in values {
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+        headers
+    }
+}
+
+/// Percent-encodes the characters that are not allowed in a header name
+fn escape_header_name(key: &str) -> String {
+    let mut escaped = String::new();
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&'*+-.^_`|~".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+impl User {
+    /// Gets who this user acts as, or `None` when `as` is not set. The other
+    /// impersonation keys are only used together with `as`.
+    pub fn impersonation(&self) -> Option<Impersonation> {
+        Some(Impersonation {
+            user: self.impersonate.clone()?,
+            uid: self.impersonate_uid.clone(),
+            groups: self.impersonate_groups.clone().unwrap_or_default(),
+            extra: self.impersonate_user_extra.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Gets where the bearer token of the user comes from, or `None` when the
+    /// user has neither `token` nor `tokenFile` set.
+    ///
+    /// Like client-go, `tokenFile` wins over `token` when both are set, so
+    /// that rotated tokens are picked up.
+    pub fn token_source(&self) -> Option<TokenSource> {
+        match (&self.token_file, &self.token) {
+            (Some(path), _) => Some(TokenSource::file(path.clone())),
+            (None, Some(token)) => Some(TokenSource::fixed(token.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Gets the PEM encoded client certificate of the user, either by decoding
+    /// `client-certificate-data` or by reading the `client-certificate` file.
+    ///
+    /// Returns `None` when neither is set, and an error when both are set.
+    pub fn client_cert_pem(&self) -> crate::errors::Result<Option<Vec<u8>>> {
+        read_data_or_file(
+            "client-certificate",
+            self.client_certificate_data.as_deref(),
+            self.client_certificate.as_deref(),
+        )
+    }
+
+    /// Gets the PEM encoded client key of the user, either by decoding
+    /// `client-key-data` or by reading the `client-key` file.
+    ///
+    /// Returns `None` when neither is set, and an error when both are set.
+    pub fn client_key_pem(&self) -> crate::errors::Result<Option<Vec<u8>>> {
+        read_data_or_file(
+            "client-key",
+            self.client_key_data.as_deref(),
+            self.client_key.as_deref(),
+        )
+    }
 }
 
 impl<'de> Deserialize<'de> for User {
@@ -44,11 +211,15 @@ impl<'de> Deserialize<'de> for User {
     {
         let map: Mapping = Deserialize::deserialize(d)?;
         let name = get_string(&map, "name")?;
+        let entry_extra = get_remainder(&map, &[("name", Kind::Other), ("user", Kind::Other)]);
         let user = get_mapping(map, "user")?;
 
         Ok(User {
             name,
             token: get_string::<D::Error>(&user, "token").ok(),
+            token_file: get_string::<D::Error>(&user, "tokenFile")
+                .map(PathBuf::from)
+                .ok(),
             username: get_string::<D::Error>(&user, "username").ok(),
             password: get_string::<D::Error>(&user, "password").ok(),
             client_certificate: get_string::<D::Error>(&user, "client-certificate")
@@ -59,12 +230,88 @@ impl<'de> Deserialize<'de> for User {
                 .map(PathBuf::from)
                 .ok(),
             client_key_data: get_string::<D::Error>(&user, "client-key-data").ok(),
+            exec: get_optional(&user, "exec")?,
+            auth_provider: get_optional(&user, "auth-provider")?,
+            impersonate: get_string::<D::Error>(&user, "as").ok(),
+            impersonate_uid: get_string::<D::Error>(&user, "as-uid").ok(),
+            impersonate_groups: get_optional(&user, "as-groups")?,
+            impersonate_user_extra: get_optional(&user, "as-user-extra")?,
+            extensions: get_optional(&user, "extensions")?.unwrap_or_default(),
+            extra: get_remainder(&user, USER_KEYS),
+            entry_extra,
         })
 
         // User::try_from(map)
     }
 }
 
+impl Serialize for User {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut user = Mapping::new();
+        if let Some(impersonate) = &self.impersonate {
+            set_string(&mut user, "as", impersonate);
+        }
+        if let Some(impersonate_groups) = &self.impersonate_groups {
+            set_serialized::<_, S::Error>(&mut user, "as-groups", impersonate_groups)?;
+        }
+        if let Some(impersonate_uid) = &self.impersonate_uid {
+            set_string(&mut user, "as-uid", impersonate_uid);
+        }
+        if let Some(impersonate_user_extra) = &self.impersonate_user_extra {
+            set_serialized::<_, S::Error>(&mut user, "as-user-extra", impersonate_user_extra)?;
+        }
+        if let Some(auth_provider) = &self.auth_provider {
+            set_serialized::<_, S::Error>(&mut user, "auth-provider", auth_provider)?;
+        }
+        if let Some(client_certificate) = &self.client_certificate {
+            set_path::<S::Error>(&mut user, "client-certificate", client_certificate)?;
+        }
+        if let Some(client_certificate_data) = &self.client_certificate_data {
+            set_string(
+                &mut user,
+                "client-certificate-data",
+                client_certificate_data,
+            );
+        }
+        if let Some(client_key) = &self.client_key {
+            set_path::<S::Error>(&mut user, "client-key", client_key)?;
+        }
+        if let Some(client_key_data) = &self.client_key_data {
+            set_string(&mut user, "client-key-data", client_key_data);
+        }
+        if let Some(exec) = &self.exec {
+            set_serialized::<_, S::Error>(&mut user, "exec", exec)?;
+        }
+        if !self.extensions.is_empty() {
+            set_serialized::<_, S::Error>(&mut user, "extensions", &self.extensions)?;
+        }
+        if let Some(password) = &self.password {
+            set_string(&mut user, "password", password);
+        }
+        if let Some(token) = &self.token {
+            set_string(&mut user, "token", token);
+        }
+        if let Some(token_file) = &self.token_file {
+            set_path::<S::Error>(&mut user, "tokenFile", token_file)?;
+        }
+        if let Some(username) = &self.username {
+            set_string(&mut user, "username", username);
+        }
+        set_extra(&mut user, &self.extra);
+
+        let mut map = Mapping::new();
+        set_string(&mut map, "name", &self.name);
+        set_mapping(&mut map, "user", user);
+        for (key, value) in &self.entry_extra {
+            map.insert(key.clone(), value.clone());
+        }
+        map.serialize(s)
+    }
+}
+
 /*
 impl TryFrom<Mapping> for User {
     type Error = de::Error;
@@ -0,0 +1,77 @@
+//! Writing yaml in the block style `kubectl` writes kubeconfig files in
+
+use crate::errors::*;
+use serde_yaml::Value;
+
+/// Renders a value as block style lines, indented by `indent`. Like `kubectl`,
+/// the keys of mappings are sorted, lists are written at the indentation of
+/// their key and strings are only quoted when they have to be.
+pub(crate) fn render(value: &Value, indent: usize) -> Result<Vec<String>> {
+    let prefix = " ".repeat(indent);
+    let mut lines = Vec::new();
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+            for (key, value) in entries {
+                let key = scalar(key)?;
+                match value {
+                    Value::Mapping(map) if !map.is_empty() => {
+                        lines.push(format!("{}{}:", prefix, key));
+                        lines.extend(render(value, indent + 2)?);
+                    }
+                    Value::Sequence(items) if !items.is_empty() => {
+                        lines.push(format!("{}{}:", prefix, key));
+                        lines.extend(render(value, indent)?);
+                    }
+                    _ => lines.push(format!("{}{}: {}", prefix, key, scalar(value)?)),
+                }
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for item in items {
+                match item {
+                    Value::Mapping(map) if !map.is_empty() => {
+                        lines.extend(as_item(render(item, indent + 2)?, indent));
+                    }
+                    Value::Sequence(items) if !items.is_empty() => {
+                        lines.extend(as_item(render(item, indent + 2)?, indent));
+                    }
+                    _ => lines.push(format!("{}- {}", prefix, scalar(item)?)),
+                }
+            }
+        }
+        _ => lines.push(format!("{}{}", prefix, scalar(value)?)),
+    }
+    Ok(lines)
+}
+
+/// Renders a value that fits on a single line: a scalar, or an empty mapping
+/// or list. Strings are left unquoted when yaml reads them back the same.
+pub(crate) fn scalar(value: &Value) -> Result<String> {
+    match value {
+        Value::Mapping(map) if map.is_empty() => return Ok("{}".to_string()),
+        Value::Sequence(items) if items.is_empty() => return Ok("[]".to_string()),
+        Value::String(s)
+            if !s.contains('\n')
+                && serde_yaml::from_str::<Value>(s).ok().as_ref() == Some(value) =>
+        {
+            return Ok(s.clone())
+        }
+        _ => {}
+    }
+    let yaml = serde_yaml::to_string(value)?;
+    let yaml = yaml.trim_start_matches("---").trim();
+    if yaml.contains('\n') {
+        bail!("value can not be written on a single line");
+    }
+    Ok(yaml.to_string())
+}
+
+/// Turns the first line, indented by `indent + 2`, into a list item
+fn as_item(mut lines: Vec<String>, indent: usize) -> Vec<String> {
+    if let Some(first) = lines.first_mut() {
+        *first = format!("{}- {}", " ".repeat(indent), &first[indent + 2..]);
+    }
+    lines
+}
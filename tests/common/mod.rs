@@ -0,0 +1,164 @@
+//! Helpers shared by the integration tests that need real TLS credentials
+
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use kube_conf::errors::*;
+use kube_conf::Config;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair, KeyUsagePurpose};
+use std::path::Path;
+
+/// A certificate authority with a server and a client certificate signed by it
+pub struct Pki {
+    pub ca_pem: String,
+    pub server_cert_pem: String,
+    pub server_key_pem: String,
+    pub client_cert_pem: String,
+    pub client_key_pem: String,
+}
+
+impl Pki {
+    pub fn generate() -> Pki {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "kube-conf test ca");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.key_usages = vec![KeyUsagePurpose::KeyCertSign];
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        server_params
+            .distinguished_name
+            .push(DnType::CommonName, "localhost");
+        let server = server_params.signed_by(&server_key, &ca, &ca_key).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(vec!["developer".to_string()]).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "developer");
+        let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        Pki {
+            ca_pem: ca.pem(),
+            server_cert_pem: server.pem(),
+            server_key_pem: server_key.serialize_pem(),
+            client_cert_pem: client.pem(),
+            client_key_pem: client_key.serialize_pem(),
+        }
+    }
+}
+
+/// Writes a kubeconfig with a single context pointing at `server`, using the
+/// client certificate of `pki`. The cluster trusts `ca_pem` if given, and skips
+/// verification of the server if `insecure` is set.
+pub fn write_config(
+    path: &Path,
+    server: &str,
+    pki: &Pki,
+    ca_pem: Option<&str>,
+    insecure: bool,
+) -> Result<Config> {
+    let mut cluster = format!("    server: {}\n", server);
+    if let Some(ca_pem) = ca_pem {
+        cluster.push_str(&format!(
+            "    certificate-authority-data: {}\n",
+            STANDARD.encode(ca_pem)
+        ));
+    }
+    if insecure {
+        cluster.push_str("    insecure-skip-tls-verify: true\n");
+    }
+
+    let config = format!(
+        r#"apiVersion: v1
+clusters:
+- cluster:
+{}  name: local
+contexts:
+- context:
+    cluster: local
+    user: developer
+  name: local
+current-context: local
+kind: Config
+users:
+- name: developer
+  user:
+    client-certificate-data: {}
+    client-key-data: {}
+"#,
+        cluster,
+        STANDARD.encode(&pki.client_cert_pem),
+        STANDARD.encode(&pki.client_key_pem)
+    );
+    std::fs::write(path, config)?;
+
+    Config::load(path)
+}
+
+/// Starts an HTTPS server using the server certificate of `pki`, answering
+/// "ok" to every request. The head of each request is sent to the receiver,
+/// followed by an `sni:` line with the name the client sent in the SNI
+/// extension.
+#[cfg(feature = "rustls")]
+pub fn start_https_server(pki: &Pki) -> (String, std::sync::mpsc::Receiver<String>) {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::{ServerConfig, ServerConnection, StreamOwned};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from_pem_slice(pki.server_cert_pem.as_bytes()).unwrap()],
+            PrivateKeyDer::from_pem_slice(pki.server_key_pem.as_bytes()).unwrap(),
+        )
+        .unwrap();
+    let server_config = Arc::new(server_config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!(
+        "https://localhost:{}",
+        listener.local_addr().unwrap().port()
+    );
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = ServerConnection::new(server_config.clone()).unwrap();
+            let mut reader = BufReader::new(StreamOwned::new(connection, stream.unwrap()));
+
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if line.trim().is_empty() => break,
+                    Ok(_) => head.push_str(&line),
+                }
+            }
+            if head.is_empty() {
+                continue;
+            }
+            let sni = reader.get_ref().conn.server_name().unwrap_or_default();
+            head.push_str(&format!("sni: {}\r\n", sni));
+            let _ = sender.send(head);
+
+            let stream = reader.get_mut();
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+            let _ = stream.flush();
+        }
+    });
+
+    (address, receiver)
+}
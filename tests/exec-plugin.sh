@@ -0,0 +1,48 @@
+#!/bin/sh
+# A stub exec credential plugin. Prints a token built from the first argument
+# and, when EXEC_INFO_OUT is set, records the exec info it was given. When
+# RUNS_OUT is set a line is appended to it for every run. When SLEEP is set it
+# waits that many seconds before answering. When CERT_DATA and KEY_DATA are set
+# they are returned as the client certificate and key instead of a token.
+
+if [ -n "$EXEC_INFO_OUT" ]; then
+  printf '%s' "$KUBERNETES_EXEC_INFO" > "$EXEC_INFO_OUT"
+fi
+
+if [ -n "$RUNS_OUT" ]; then
+  echo run >> "$RUNS_OUT"
+fi
+
+if [ -n "$SLEEP" ]; then
+  sleep "$SLEEP"
+fi
+
+if [ "$1" = "fail" ]; then
+  echo "stub plugin failing on purpose" >&2
+  exit 1
+fi
+
+if [ -n "$CERT_DATA" ]; then
+  cat <<EOF
+{
+  "apiVersion": "client.authentication.k8s.io/v1",
+  "kind": "ExecCredential",
+  "status": {
+    "clientCertificateData": "$CERT_DATA",
+    "clientKeyData": "$KEY_DATA"
+  }
+}
+EOF
+  exit 0
+fi
+
+cat <<EOF
+{
+  "apiVersion": "client.authentication.k8s.io/v1",
+  "kind": "ExecCredential",
+  "status": {
+    "token": "token-for-$1",
+    "expirationTimestamp": "${EXPIRATION:-2099-01-01T00:00:00Z}"
+  }
+}
+EOF
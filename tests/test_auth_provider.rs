@@ -0,0 +1,101 @@
+use kube_conf::auth_provider::AuthProvider;
+use kube_conf::errors::*;
+use kube_conf::Config;
+use serde_yaml::Value;
+
+fn load_auth_provider_config() -> Result<Config> {
+    let path = format!(
+        "{}/tests/config-auth-provider.yml",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    Config::load(&path)
+}
+
+#[test]
+pub fn it_parses_the_oidc_auth_provider() -> Result<()> {
+    let config = load_auth_provider_config()?;
+    let user = config.users.first().unwrap();
+
+    let oidc = match user.auth_provider.as_ref() {
+        Some(AuthProvider::Oidc(oidc)) => oidc,
+        other => panic!("Expected an oidc auth-provider, got {:?}", other),
+    };
+    assert_eq!(oidc.client_id.as_deref(), Some("kubernetes"));
+    assert_eq!(oidc.client_secret.as_deref(), Some("some-secret"));
+    assert_eq!(
+        oidc.idp_issuer_url.as_deref(),
+        Some("https://issuer.example.com")
+    );
+    assert_eq!(oidc.refresh_token.as_deref(), Some("some-refresh-token"));
+    assert_eq!(
+        oidc.extra
+            .get(&Value::String("extra-scopes".to_string()))
+            .and_then(Value::as_str),
+        Some("groups"),
+        "Expected unknown oidc config keys to be kept"
+    );
+
+    let provider = user.auth_provider.as_ref().unwrap();
+    assert_eq!(provider.name(), "oidc");
+    assert_eq!(
+        provider.token(),
+        Some("some-id-token"),
+        "Expected the id-token to be the bearer token"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_parses_the_gcp_and_generic_auth_providers() -> Result<()> {
+    let config = load_auth_provider_config()?;
+
+    let gcp = config.users.get(1).unwrap().auth_provider.as_ref().unwrap();
+    assert!(
+        matches!(gcp, AuthProvider::Gcp(gcp) if gcp.cmd_path.as_deref() == Some("/usr/bin/gcloud")),
+        "Expected a gcp auth-provider with the cmd-path set"
+    );
+    assert_eq!(
+        gcp.token(),
+        Some("some-access-token"),
+        "Expected the access-token to be the bearer token"
+    );
+
+    let custom = config.users.get(2).unwrap().auth_provider.as_ref().unwrap();
+    assert_eq!(custom.name(), "custom");
+    assert_eq!(
+        custom.token(),
+        Some("custom-access-token"),
+        "Expected the access-token of a generic auth-provider to be the bearer token"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_the_auth_provider_back() -> Result<()> {
+    let config = load_auth_provider_config()?;
+    let value: Value = serde_yaml::from_str(&config.to_yaml_string()?)?;
+
+    let oidc = &value["users"][0]["user"]["auth-provider"];
+    assert_eq!(oidc["name"].as_str(), Some("oidc"));
+    assert_eq!(
+        oidc["config"]["id-token"].as_str(),
+        Some("some-id-token"),
+        "Expected the oidc config to be written back"
+    );
+    assert_eq!(
+        oidc["config"]["extra-scopes"].as_str(),
+        Some("groups"),
+        "Expected unknown oidc config keys to be written back"
+    );
+
+    let custom = &value["users"][2]["user"]["auth-provider"];
+    assert_eq!(
+        custom["config"]["custom-setting"].as_str(),
+        Some("abc"),
+        "Expected the generic auth-provider config to be written back"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use kube_conf::cluster::Cluster;
+use kube_conf::errors::*;
+use kube_conf::Config;
+use serde_yaml::Value;
+
+fn load_config() -> Result<Config> {
+    Config::load(format!(
+        "{}/tests/config-cluster-options.yml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+}
+
+#[test]
+pub fn it_parses_the_cluster_options() -> Result<()> {
+    let conf = load_config()?;
+    let cluster = &conf.clusters[0];
+
+    assert_eq!(
+        cluster.proxy_url.as_deref(),
+        Some("socks5://localhost:1080")
+    );
+    assert_eq!(
+        cluster.proxy()?.map(|url| url.to_string()),
+        Some("socks5://localhost:1080".to_string())
+    );
+    assert_eq!(
+        cluster.tls_server_name.as_deref(),
+        Some("kubernetes.default")
+    );
+    assert!(cluster.disable_compression);
+    assert!(cluster.extra.is_empty(), "Expected no unknown keys");
+
+    assert_eq!(cluster.extensions.len(), 1);
+    assert_eq!(
+        cluster.extensions.get_value("cluster_info").unwrap()["provider"],
+        Value::String("minikube.sigs.k8s.io".to_string())
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_the_cluster_options_back() -> Result<()> {
+    let conf = load_config()?;
+    let written: Config = serde_yaml::from_str(&conf.to_yaml_string()?)?;
+
+    let (cluster, written_cluster) = (&conf.clusters[0], &written.clusters[0]);
+    assert_eq!(written_cluster.proxy_url, cluster.proxy_url);
+    assert_eq!(written_cluster.tls_server_name, cluster.tls_server_name);
+    assert_eq!(
+        written_cluster.disable_compression,
+        cluster.disable_compression
+    );
+    assert_eq!(written_cluster.extensions, cluster.extensions);
+
+    Ok(())
+}
+
+fn parse_cluster(proxy_url: &str) -> std::result::Result<Cluster, serde_yaml::Error> {
+    serde_yaml::from_str(&format!(
+        "name: proxied\ncluster:\n  server: https://1.2.3.4\n  proxy-url: {}\n",
+        proxy_url
+    ))
+}
+
+#[test]
+pub fn it_accepts_http_https_and_socks5_proxies() -> Result<()> {
+    for proxy_url in &[
+        "http://proxy:3128",
+        "https://proxy:3128",
+        "socks5://proxy:1080",
+    ] {
+        let cluster = parse_cluster(proxy_url)?;
+        assert!(
+            cluster.proxy().is_ok(),
+            "Expected {} to be accepted",
+            proxy_url
+        );
+        let written = serde_yaml::to_string(&cluster)?;
+        assert!(
+            written.contains(&format!("proxy-url: \"{}\"\n", proxy_url))
+                || written.contains(&format!("proxy-url: {}\n", proxy_url)),
+            "Expected {} to be written as it was, got {}",
+            proxy_url,
+            written
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn it_rejects_invalid_proxies_when_they_are_used() -> Result<()> {
+    let cluster = parse_cluster("ftp://proxy:21")?;
+    let error = cluster.proxy().unwrap_err().to_string();
+    assert!(
+        error.contains("unsupported proxy-url scheme 'ftp'"),
+        "Expected the scheme to be rejected, got {}",
+        error
+    );
+
+    let cluster = parse_cluster("not a url")?;
+    let error = cluster.proxy().unwrap_err().to_string();
+    assert_eq!(error, "invalid url: 'not a url'");
+
+    Ok(())
+}
@@ -1,6 +1,12 @@
+// The tests from before clippy was part of the build are kept as they are
+#![allow(
+    clippy::bool_assert_comparison,
+    clippy::get_first,
+    clippy::needless_borrows_for_generic_args
+)]
+
 use kube_conf::errors::*;
 use kube_conf::Config;
-use serde_yaml::Value;
 use std::path::PathBuf;
 
 #[test]
@@ -54,13 +60,9 @@ pub fn it_returns_the_preferences_map() -> Result<()> {
     assert!(config.preferences.is_some(), "Preferences were not defined");
 
     let preferences = config.preferences.unwrap();
-    let value = preferences.get(&Value::String("fooValue".to_string()));
-    assert!(value.is_some(), "fooValue not found in preferences mapping");
-    assert_eq!(
-        value.unwrap().as_str().unwrap(),
-        "abc",
-        "Preferences were not defined"
-    );
+    let value: Option<String> = preferences.get("fooValue")?;
+    assert!(value.is_some(), "fooValue not found in preferences");
+    assert_eq!(value.unwrap(), "abc", "Preferences were not defined");
 
     Ok(())
 }
@@ -83,7 +85,7 @@ pub fn it_returns_the_cluster_set() -> Result<()> {
     );
     assert_eq!(
         cluster1.certificate_authority.as_ref().unwrap(),
-        &PathBuf::from("fake-ca-file"),
+        &PathBuf::from(format!("{}/tests/fake-ca-file", env!("CARGO_MANIFEST_DIR"))),
         "Expected the first cluster to have a CA configured"
     );
     assert_eq!(
@@ -153,7 +155,10 @@ pub fn it_returns_the_users_set() -> Result<()> {
     );
     assert_eq!(
         user1.client_certificate.as_ref().unwrap(),
-        &PathBuf::from("fake-cert-file"),
+        &PathBuf::from(format!(
+            "{}/tests/fake-cert-file",
+            env!("CARGO_MANIFEST_DIR")
+        )),
         "Expected the first user to have the correct client-certificate configured"
     );
 
@@ -163,7 +168,10 @@ pub fn it_returns_the_users_set() -> Result<()> {
     );
     assert_eq!(
         user1.client_key.as_ref().unwrap(),
-        &PathBuf::from("fake-key-file"),
+        &PathBuf::from(format!(
+            "{}/tests/fake-key-file",
+            env!("CARGO_MANIFEST_DIR")
+        )),
         "Expected the first user to have the correct client-key configured"
     );
 
@@ -238,7 +246,7 @@ pub fn it_gets_the_cluster_from_a_context() -> Result<()> {
     );
     assert_eq!(
         cluster.certificate_authority.as_ref().unwrap(),
-        &PathBuf::from("fake-ca-file"),
+        &PathBuf::from(format!("{}/tests/fake-ca-file", env!("CARGO_MANIFEST_DIR"))),
         "Cluster of context did not have the expected certificate-authority"
     );
     assert_eq!(
@@ -270,7 +278,10 @@ pub fn it_gets_the_user_from_a_context() -> Result<()> {
     );
     assert_eq!(
         user.client_certificate.as_ref().unwrap(),
-        &PathBuf::from("fake-cert-file"),
+        &PathBuf::from(format!(
+            "{}/tests/fake-cert-file",
+            env!("CARGO_MANIFEST_DIR")
+        )),
         "User of context did not have the expected client-certificate"
     );
     assert!(
@@ -279,9 +290,372 @@ pub fn it_gets_the_user_from_a_context() -> Result<()> {
     );
     assert_eq!(
         user.client_key.as_ref().unwrap(),
-        &PathBuf::from("fake-key-file"),
+        &PathBuf::from(format!(
+            "{}/tests/fake-key-file",
+            env!("CARGO_MANIFEST_DIR")
+        )),
         "User of context did not have the expected client-key"
     );
 
     Ok(())
 }
+
+#[test]
+pub fn it_merges_multiple_config_files() -> Result<()> {
+    let paths = vec![
+        format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")),
+        format!("{}/tests/config-extra.yml", env!("CARGO_MANIFEST_DIR")),
+    ];
+    let config = Config::load_all(&paths)?;
+
+    assert_eq!(
+        config.current_context.as_ref().unwrap(),
+        "dev-frontend",
+        "Expected the first file to decide the current context"
+    );
+    assert_eq!(
+        config.clusters.len(),
+        3,
+        "Expected 3 clusters in the merged cluster set"
+    );
+    assert_eq!(
+        config.clusters.get(1).unwrap().server,
+        "https://5.6.7.8",
+        "Expected the first file to win when a cluster is defined twice"
+    );
+    assert_eq!(
+        config.clusters.get(2).unwrap().name,
+        "staging",
+        "Expected clusters from the second file to be appended"
+    );
+    assert_eq!(
+        config.contexts.len(),
+        4,
+        "Expected 4 contexts in the merged context set"
+    );
+    assert_eq!(
+        config.users.len(),
+        3,
+        "Expected 3 users in the merged user set"
+    );
+    assert!(
+        config.users.first().unwrap().token.is_none(),
+        "Expected the first file to win when a user is defined twice"
+    );
+
+    let preferences = config.preferences.unwrap();
+    assert_eq!(
+        preferences.get::<String>("fooValue")?.unwrap(),
+        "abc",
+        "Expected the first file to win when a preference is defined twice"
+    );
+    assert!(
+        preferences.get_value("barValue").is_some(),
+        "Expected preferences from the second file to be merged"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_skips_missing_files_when_merging() -> Result<()> {
+    let paths = vec![
+        "/path/that/does/not/exist".to_string(),
+        format!("{}/tests/config-extra.yml", env!("CARGO_MANIFEST_DIR")),
+    ];
+    let config = Config::load_all(&paths)?;
+
+    assert_eq!(
+        config.current_context.as_ref().unwrap(),
+        "staging",
+        "Expected the current context of the only existing file"
+    );
+    assert_eq!(
+        config.clusters.len(),
+        2,
+        "Expected 2 clusters in the cluster set"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_keeps_raw_paths_when_asked_to() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load_raw(&path)?;
+
+    assert_eq!(
+        config.clusters.first().unwrap().certificate_authority,
+        Some(PathBuf::from("fake-ca-file")),
+        "Expected the certificate-authority as written in the file"
+    );
+    assert_eq!(
+        config.users.first().unwrap().client_key,
+        Some(PathBuf::from("fake-key-file")),
+        "Expected the client-key as written in the file"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_resolves_paths_against_the_config_directory() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config");
+    std::fs::write(
+        &path,
+        r#"clusters:
+- cluster:
+    certificate-authority: certs/ca.crt
+    server: https://1.2.3.4
+  name: relative
+- cluster:
+    certificate-authority: /etc/kubernetes/ca.crt
+    server: https://5.6.7.8
+  name: absolute
+contexts: []
+users:
+- name: home
+  user:
+    client-certificate: ~/certs/client.crt
+    client-key: ../client.key
+"#,
+    )?;
+
+    let config = Config::load(&path)?;
+    assert_eq!(
+        config.clusters.first().unwrap().certificate_authority,
+        Some(dir.path().join("certs/ca.crt")),
+        "Expected a relative path to be resolved against the config directory"
+    );
+    assert_eq!(
+        config.clusters.get(1).unwrap().certificate_authority,
+        Some(PathBuf::from("/etc/kubernetes/ca.crt")),
+        "Expected an absolute path to be kept"
+    );
+
+    let user = config.users.first().unwrap();
+    let client_certificate = user.client_certificate.as_ref().unwrap();
+    assert!(
+        client_certificate.is_absolute() && client_certificate.ends_with("certs/client.crt"),
+        "Expected ~ to be expanded to the home directory"
+    );
+    assert_eq!(
+        user.client_key,
+        Some(dir.path().join("../client.key")),
+        "Expected a relative path to be resolved against the config directory"
+    );
+
+    config.save(&path)?;
+    let saved = Config::load_raw(&path)?;
+    assert_eq!(
+        saved.clusters.first().unwrap().certificate_authority,
+        Some(PathBuf::from("certs/ca.crt")),
+        "Expected paths inside the config directory to be saved as relative paths"
+    );
+    assert_eq!(
+        saved.clusters.get(1).unwrap().certificate_authority,
+        Some(PathBuf::from("/etc/kubernetes/ca.crt")),
+        "Expected paths outside the config directory to be saved as they are"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_saves_paths_the_way_they_were_written() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config");
+    let absolute = dir.path().join("certs/ca.crt");
+    std::fs::write(
+        &path,
+        format!(
+            r#"clusters:
+- cluster:
+    certificate-authority: {}
+    server: https://1.2.3.4
+  name: absolute
+contexts: []
+users:
+- name: home
+  user:
+    client-certificate: ~/certs/client.crt
+    client-key: ../client.key
+"#,
+            absolute.display()
+        ),
+    )?;
+
+    let config = Config::load(&path)?;
+    config.save(&path)?;
+    let saved = Config::load_raw(&path)?;
+    assert_eq!(
+        saved.clusters.first().unwrap().certificate_authority,
+        Some(absolute),
+        "Expected an absolute path inside the config directory to stay absolute"
+    );
+    let user = saved.users.first().unwrap();
+    assert_eq!(
+        user.client_certificate,
+        Some(PathBuf::from("~/certs/client.crt")),
+        "Expected a path starting with ~ to be saved the way it was written"
+    );
+    assert_eq!(user.client_key, Some(PathBuf::from("../client.key")));
+
+    let moved = dir.path().join("moved/config");
+    std::fs::create_dir(dir.path().join("moved"))?;
+    config.save(&moved)?;
+    assert_eq!(
+        Config::load_raw(&moved)?.users.first().unwrap().client_key,
+        Some(dir.path().join("../client.key")),
+        "Expected a relative path to be saved resolved when it would point elsewhere"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_looks_up_entries_by_name() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+
+    assert_eq!(
+        config.cluster("scratch").map(|c| c.server.as_str()),
+        Some("https://5.6.7.8")
+    );
+    assert_eq!(
+        config.context("exp-scratch").map(|c| c.user.as_str()),
+        Some("experimenter")
+    );
+    assert_eq!(
+        config
+            .user("experimenter")
+            .and_then(|u| u.username.as_deref()),
+        Some("exp")
+    );
+    assert!(config.cluster("missing").is_none());
+    assert!(config.context("missing").is_none());
+    assert!(config.user("missing").is_none());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_looks_up_entries_after_the_lists_are_changed() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let mut config = Config::load(&path)?;
+
+    let removed = config.clusters.remove(0);
+    let mut renamed = config.users[0].clone();
+    renamed.name = "renamed".to_string();
+    config.users[0] = renamed;
+
+    assert!(config.cluster(&removed.name).is_none());
+    assert_eq!(
+        config.cluster("scratch").map(|c| c.server.as_str()),
+        Some("https://5.6.7.8")
+    );
+    assert!(config.user("developer").is_none());
+    assert!(config.user("renamed").is_some());
+
+    config.reindex();
+    assert!(config.user("renamed").is_some());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_looks_up_the_first_entry_with_a_name() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let mut config = Config::load(&path)?;
+
+    let mut duplicate = config.clusters[0].clone();
+    duplicate.server = "https://duplicate".to_string();
+    config.clusters.push(duplicate);
+    config.reindex();
+
+    assert_ne!(
+        config.cluster(&config.clusters[0].name).unwrap().server,
+        "https://duplicate"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_minifies_to_the_current_context() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+    let minified = config.minify(None)?;
+
+    assert_eq!(minified.current_context.as_deref(), Some("dev-frontend"));
+    assert_eq!(minified.contexts.len(), 1);
+    assert_eq!(minified.clusters.len(), 1);
+    assert_eq!(minified.clusters[0].name, "development");
+    assert_eq!(minified.users.len(), 1);
+    assert_eq!(minified.users[0].name, "developer");
+    assert_eq!(minified.preferences, config.preferences);
+    assert_eq!(
+        minified.source(),
+        None,
+        "Expected the minified config not to be tied to the file"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_minifies_to_the_given_context() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+    let minified = config.minify(Some("exp-scratch"))?;
+
+    assert_eq!(minified.current_context.as_deref(), Some("exp-scratch"));
+    assert_eq!(
+        minified
+            .get_current_context()
+            .unwrap()
+            .get_cluster(&minified)
+            .unwrap()
+            .server,
+        "https://5.6.7.8"
+    );
+    assert!(minified.user("experimenter").is_some());
+    assert!(minified.user("developer").is_none());
+
+    assert_eq!(
+        config.minify(Some("missing")).unwrap_err().to_string(),
+        "context 'missing' was not found in the config"
+    );
+
+    let mut config = config;
+    config.current_context = None;
+    assert_eq!(
+        config.minify(None).unwrap_err().to_string(),
+        "current-context must be set to minify the config"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_builds_a_config_from_scratch() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let cluster = Config::load(&path)?.cluster("development").unwrap().clone();
+
+    let mut config = Config::new();
+    config.replace_cluster(cluster);
+
+    assert_eq!(
+        config.cluster("development").unwrap().server,
+        "https://1.2.3.4"
+    );
+    let yaml = config.to_yaml_string()?;
+    assert!(yaml.starts_with("apiVersion: v1\n"));
+    assert!(yaml.contains("kind: Config\n"));
+    assert!(Config::default()
+        .to_yaml_string()?
+        .starts_with("clusters: []\n"));
+
+    Ok(())
+}
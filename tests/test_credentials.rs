@@ -0,0 +1,94 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use std::path::PathBuf;
+
+fn load_config() -> Result<Config> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    Config::load(&path)
+}
+
+#[test]
+pub fn it_decodes_inline_credential_data() -> Result<()> {
+    let config = load_config()?;
+    let mut cluster = config.clusters.first().unwrap().clone();
+    cluster.certificate_authority = None;
+    cluster.certificate_authority_data =
+        Some("LS0tLS1CRUdJTi\n BDRVJUSUZJQ0FURS0tLS0t".to_string());
+
+    assert_eq!(
+        cluster.ca_pem()?,
+        Some(b"-----BEGIN CERTIFICATE-----".to_vec()),
+        "Expected the certificate-authority-data to be decoded"
+    );
+
+    let mut user = config.users.first().unwrap().clone();
+    user.client_key = None;
+    user.client_key_data = Some("a2V5".to_string());
+    assert_eq!(
+        user.client_key_pem()?,
+        Some(b"key".to_vec()),
+        "Expected the client-key-data to be decoded"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_reads_credential_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cert_path = dir.path().join("client.crt");
+    std::fs::write(&cert_path, "certificate")?;
+
+    let config = load_config()?;
+    let mut user = config.users.first().unwrap().clone();
+    user.client_certificate = Some(cert_path);
+
+    assert_eq!(
+        user.client_cert_pem()?,
+        Some(b"certificate".to_vec()),
+        "Expected the client-certificate file to be read"
+    );
+    assert_eq!(
+        config.users.get(1).unwrap().client_cert_pem()?,
+        None,
+        "Expected no client certificate for a user without one"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_errors_for_invalid_credentials() -> Result<()> {
+    let config = load_config()?;
+
+    let cluster = config.clusters.first().unwrap();
+    let result = cluster.ca_pem();
+    assert!(
+        result.is_err(),
+        "Expected return to be an error, but was success"
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "could not read file: '{}'",
+            PathBuf::from(format!("{}/tests/fake-ca-file", env!("CARGO_MANIFEST_DIR"))).display()
+        )
+    );
+
+    let mut cluster = cluster.clone();
+    cluster.certificate_authority_data = Some("bm90LXVzZWQ=".to_string());
+    assert_eq!(
+        cluster.ca_pem().unwrap_err().to_string(),
+        "both 'certificate-authority' and 'certificate-authority-data' are set"
+    );
+
+    let mut user = config.users.first().unwrap().clone();
+    user.client_certificate = None;
+    user.client_certificate_data = Some("not base64!".to_string());
+    assert_eq!(
+        user.client_cert_pem().unwrap_err().to_string(),
+        "invalid base64 in 'client-certificate-data'"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use kube_conf::document::ConfigDocument;
+use kube_conf::errors::*;
+use std::fs::{read_to_string, write};
+
+fn fixture() -> String {
+    read_to_string(format!(
+        "{}/tests/config-comments.yml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+/// The lines of `after` that are not in `before`, and the other way around
+fn changed_lines(before: &str, after: &str) -> (Vec<String>, Vec<String>) {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    (
+        after
+            .iter()
+            .filter(|l| !before.contains(l))
+            .map(|l| l.to_string())
+            .collect(),
+        before
+            .iter()
+            .filter(|l| !after.contains(l))
+            .map(|l| l.to_string())
+            .collect(),
+    )
+}
+
+#[test]
+pub fn it_keeps_the_text_without_edits() -> Result<()> {
+    let document = ConfigDocument::parse(&fixture())?;
+
+    assert_eq!(document.to_yaml_string(), fixture());
+    assert_eq!(document.config().clusters.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_changes_only_the_current_context_line() -> Result<()> {
+    let mut document = ConfigDocument::parse(&fixture())?;
+    document.use_context("exp-scratch")?;
+
+    assert_eq!(
+        changed_lines(&fixture(), &document.to_yaml_string()),
+        (
+            vec!["current-context: exp-scratch  # the default".to_string()],
+            vec!["current-context: dev-frontend  # the default".to_string()]
+        )
+    );
+    assert_eq!(
+        document.config().current_context.as_deref(),
+        Some("exp-scratch")
+    );
+    assert_eq!(
+        document.use_context("missing").unwrap_err().to_string(),
+        "context 'missing' was not found in the config"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_sets_the_namespace_of_a_context() -> Result<()> {
+    let mut document = ConfigDocument::parse(&fixture())?;
+    document.set_namespace("dev-frontend", "backend")?;
+    document.set_namespace("exp-scratch", "default")?;
+
+    assert_eq!(
+        changed_lines(&fixture(), &document.to_yaml_string()),
+        (
+            vec![
+                "      namespace: backend".to_string(),
+                "      namespace: default".to_string()
+            ],
+            vec!["      namespace: frontend".to_string()]
+        )
+    );
+    let text = document.to_yaml_string();
+    assert!(
+        text.contains("      cluster: scratch\n      namespace: default\n      user: experimenter"),
+        "Expected the namespace to be added between the other keys"
+    );
+    assert_eq!(
+        document
+            .config()
+            .context("exp-scratch")
+            .unwrap()
+            .namespace
+            .as_deref(),
+        Some("default")
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_adds_and_replaces_entries() -> Result<()> {
+    let mut document = ConfigDocument::parse(&fixture())?;
+
+    let mut cluster = document.config().cluster("scratch").unwrap().clone();
+    cluster.name = "staging".to_string();
+    cluster.server = "https://9.9.9.9".to_string();
+    assert!(document.replace_cluster(cluster.clone())?.is_none());
+
+    let mut context = document.config().context("exp-scratch").unwrap().clone();
+    context.name = "staging".to_string();
+    context.cluster = "staging".to_string();
+    assert!(document.replace_context(context)?.is_none());
+
+    cluster.name = "scratch".to_string();
+    let replaced = document.replace_cluster(cluster)?.unwrap();
+    assert_eq!(replaced.server, "https://5.6.7.8");
+
+    let text = document.to_yaml_string();
+    assert!(text.contains(
+        "# Throwaway cluster for experiments\n- cluster:\n    insecure-skip-tls-verify: true\n    server: https://9.9.9.9\n  name: scratch\n"
+    ));
+    assert!(text.contains("# The shared development cluster\n"));
+    assert!(text.contains("    server: https://1.2.3.4 # behind the vpn\n"));
+    assert!(
+        text.contains(
+            "      user: experimenter\n  - context:\n      cluster: staging\n      user: experimenter\n    name: staging\n"
+        ),
+        "Expected the new context to use the indentation of the list"
+    );
+    assert!(text.starts_with("# Managed by hand, see the team wiki\n"));
+
+    let config = document.config();
+    assert_eq!(
+        config.clusters.iter().map(|c| &c.name).collect::<Vec<_>>(),
+        vec!["development", "scratch", "staging"]
+    );
+    assert_eq!(config.cluster("scratch").unwrap().server, "https://9.9.9.9");
+    assert_eq!(config.context("staging").unwrap().cluster, "staging");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_changes_only_the_keys_of_a_replaced_entry() -> Result<()> {
+    let mut document = ConfigDocument::parse(&fixture())?;
+
+    let mut cluster = document.config().cluster("development").unwrap().clone();
+    cluster.certificate_authority = Some("other-ca-file".into());
+    document.replace_cluster(cluster)?;
+
+    let mut context = document.config().context("dev-frontend").unwrap().clone();
+    context.namespace = Some("backend".to_string());
+    document.replace_context(context)?;
+
+    let mut user = document.config().user("experimenter").unwrap().clone();
+    user.username = None;
+    user.password = None;
+    user.token = Some("some-token".to_string());
+    document.replace_user(user)?;
+
+    assert_eq!(
+        changed_lines(&fixture(), &document.to_yaml_string()),
+        (
+            vec![
+                "    certificate-authority: other-ca-file".to_string(),
+                "      namespace: backend".to_string(),
+                "    token: some-token".to_string(),
+            ],
+            vec![
+                "    certificate-authority: fake-ca-file".to_string(),
+                "      namespace: frontend".to_string(),
+                "    password: some-password".to_string(),
+                "    username: exp".to_string(),
+            ]
+        )
+    );
+    let text = document.to_yaml_string();
+    assert!(
+        text.contains("# The shared development cluster\n- cluster:\n    certificate-authority: other-ca-file\n    server: https://1.2.3.4 # behind the vpn\n  name: development\n"),
+        "Expected the comments and the order of the keys to be kept"
+    );
+    assert!(text
+        .contains("      user: developer\n      cluster: development\n      namespace: backend\n"));
+    assert_eq!(
+        document
+            .config()
+            .user("experimenter")
+            .unwrap()
+            .token
+            .as_deref(),
+        Some("some-token")
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_removes_entries_with_their_comments() -> Result<()> {
+    let mut document = ConfigDocument::parse(&fixture())?;
+
+    let removed = document.remove_cluster("scratch")?.unwrap();
+    assert_eq!(removed.server, "https://5.6.7.8");
+    assert!(document.remove_cluster("missing")?.is_none());
+    document.remove_user("developer")?;
+    document.remove_user("experimenter")?;
+
+    let text = document.to_yaml_string();
+    assert!(!text.contains("Throwaway"));
+    assert!(!text.contains("https://5.6.7.8"));
+    assert!(text.contains("# The shared development cluster\n"));
+    assert!(
+        text.ends_with("kind: Config\nusers: []\n"),
+        "Expected an empty list to be written as []"
+    );
+    assert_eq!(document.config().clusters.len(), 1);
+    assert!(document.config().users.is_empty());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_adds_to_empty_lists() -> Result<()> {
+    let mut document = ConfigDocument::parse("clusters: []\ncontexts: []\nusers: []\n")?;
+    let cluster = ConfigDocument::parse(&fixture())?
+        .config()
+        .cluster("development")
+        .unwrap()
+        .clone();
+    let user = ConfigDocument::parse(&fixture())?
+        .config()
+        .user("developer")
+        .unwrap()
+        .clone();
+
+    document.replace_cluster(cluster)?;
+    document.replace_user(user)?;
+
+    assert!(document
+        .to_yaml_string()
+        .starts_with("clusters:\n- cluster:\n    certificate-authority: fake-ca-file\n    server: https://1.2.3.4\n  name: development\n"));
+    assert!(document
+        .to_yaml_string()
+        .contains("contexts: []\nusers:\n- name: developer\n  user:\n"));
+    assert_eq!(document.config().users.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_an_empty_mapping_for_a_user_without_credentials() -> Result<()> {
+    let mut document = ConfigDocument::parse(
+        "clusters: []\ncontexts: []\nusers:\n- name: ci\n  user:\n    token: abc\n",
+    )?;
+    let mut user = document.config().user("ci").unwrap().clone();
+    user.token = None;
+
+    document.replace_user(user)?;
+
+    assert_eq!(
+        document.to_yaml_string(),
+        "clusters: []\ncontexts: []\nusers:\n- name: ci\n  user: {}\n"
+    );
+    assert!(document.config().user("ci").unwrap().token.is_none());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_refuses_to_overwrite_changes_made_since_loading() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config");
+    write(&path, fixture())?;
+
+    let mut document = ConfigDocument::load(&path)?;
+    document.use_context("exp-scratch")?;
+    document.save()?;
+    assert_eq!(read_to_string(&path)?, document.to_yaml_string());
+
+    write(&path, fixture().replace("username: exp", "username: other"))?;
+    document.set_namespace("exp-scratch", "default")?;
+    assert_eq!(
+        document.save().unwrap_err().to_string(),
+        format!(
+            "config file '{}' changed since it was loaded",
+            path.display()
+        )
+    );
+
+    Ok(())
+}
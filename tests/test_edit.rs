@@ -0,0 +1,216 @@
+use kube_conf::edit::{ClusterPatch, ContextPatch, UserPatch};
+use kube_conf::errors::*;
+use kube_conf::Config;
+
+fn load_config() -> Result<Config> {
+    Config::load(format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")))
+}
+
+#[test]
+pub fn it_replaces_entries_by_name() -> Result<()> {
+    let mut config = load_config()?;
+
+    let mut cluster = config.cluster("scratch").unwrap().clone();
+    cluster.server = "https://9.9.9.9".to_string();
+    let replaced = config.replace_cluster(cluster.clone()).unwrap();
+    assert_eq!(replaced.server, "https://5.6.7.8");
+    assert_eq!(
+        config.clusters.len(),
+        2,
+        "Expected the cluster to be replaced"
+    );
+    assert_eq!(config.clusters[1].server, "https://9.9.9.9");
+
+    cluster.name = "new-cluster".to_string();
+    assert!(config.replace_cluster(cluster).is_none());
+    assert_eq!(config.clusters.len(), 3);
+    assert_eq!(
+        config.cluster("new-cluster").map(|c| c.server.as_str()),
+        Some("https://9.9.9.9")
+    );
+
+    let mut context = config.context("exp-scratch").unwrap().clone();
+    context.name = "new-context".to_string();
+    context.cluster = "new-cluster".to_string();
+    assert!(config.replace_context(context).is_none());
+    assert_eq!(
+        config
+            .context("new-context")
+            .unwrap()
+            .get_cluster(&config)
+            .unwrap()
+            .name,
+        "new-cluster"
+    );
+
+    let mut user = config.user("developer").unwrap().clone();
+    user.token = Some("new-token".to_string());
+    assert!(config.replace_user(user).is_some());
+    assert_eq!(
+        config.user("developer").unwrap().token.as_deref(),
+        Some("new-token")
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_upserts_only_the_given_fields() -> Result<()> {
+    let mut config = load_config()?;
+
+    let before = config
+        .upsert_cluster(
+            "development",
+            ClusterPatch {
+                server: Some("https://9.9.9.9".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(before.server, "https://1.2.3.4");
+    let cluster = config.cluster("development").unwrap();
+    assert_eq!(cluster.server, "https://9.9.9.9");
+    assert_eq!(
+        cluster.certificate_authority, before.certificate_authority,
+        "Expected the fields that were not given to be kept"
+    );
+
+    config.upsert_cluster(
+        "development",
+        ClusterPatch {
+            insecure_skip_tls_verify: Some(true),
+            ..Default::default()
+        },
+    );
+    let cluster = config.cluster("development").unwrap();
+    assert!(cluster.insecure_skip_tls_verify);
+    assert!(
+        cluster.certificate_authority.is_none(),
+        "Expected skipping verification to clear the certificate authority"
+    );
+
+    assert!(config
+        .upsert_context(
+            "new-context",
+            ContextPatch {
+                cluster: Some("development".to_string()),
+                user: Some("developer".to_string()),
+                ..Default::default()
+            },
+        )
+        .is_none());
+    config.upsert_context(
+        "new-context",
+        ContextPatch {
+            namespace: Some("backend".to_string()),
+            ..Default::default()
+        },
+    );
+    let context = config.context("new-context").unwrap();
+    assert_eq!(context.cluster, "development");
+    assert_eq!(context.namespace.as_deref(), Some("backend"));
+
+    config.upsert_user(
+        "experimenter",
+        UserPatch {
+            token: Some("new-token".to_string()),
+            ..Default::default()
+        },
+    );
+    let user = config.user("experimenter").unwrap();
+    assert_eq!(user.token.as_deref(), Some("new-token"));
+    assert!(
+        user.username.is_none() && user.password.is_none(),
+        "Expected setting a token to clear basic auth"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_reports_contexts_left_referring_to_a_removed_cluster() -> Result<()> {
+    let mut config = load_config()?;
+
+    let removed = config.remove_cluster("scratch").unwrap();
+    assert_eq!(removed.entry.name, "scratch");
+    assert_eq!(removed.referenced_by, vec!["exp-scratch"]);
+    assert_eq!(
+        removed.warnings(),
+        vec!["context 'exp-scratch' still refers to the removed entry"]
+    );
+    assert!(config.cluster("scratch").is_none());
+    assert!(config.remove_cluster("scratch").is_none());
+
+    let removed = config.remove_user("experimenter").unwrap();
+    assert_eq!(removed.referenced_by, vec!["exp-scratch"]);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_warns_when_removing_the_current_context() -> Result<()> {
+    let mut config = load_config()?;
+
+    let removed = config.remove_context("exp-scratch").unwrap();
+    assert!(!removed.was_current);
+    assert!(removed.warnings().is_empty());
+
+    let removed = config.remove_context("dev-frontend").unwrap();
+    assert!(removed.was_current);
+    assert_eq!(removed.warnings().len(), 1);
+    assert_eq!(
+        config.current_context.as_deref(),
+        Some("dev-frontend"),
+        "Expected current-context to be left as it was, like kubectl"
+    );
+    assert!(config.get_current_context().is_none());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_renames_contexts() -> Result<()> {
+    let mut config = load_config()?;
+
+    config.rename_context("dev-frontend", "frontend")?;
+    assert!(config.context("dev-frontend").is_none());
+    assert!(config.context("frontend").is_some());
+    assert_eq!(
+        config.current_context.as_deref(),
+        Some("frontend"),
+        "Expected current-context to follow the rename"
+    );
+
+    config.rename_context("exp-scratch", "scratch")?;
+    assert_eq!(config.current_context.as_deref(), Some("frontend"));
+
+    assert_eq!(
+        config
+            .rename_context("scratch", "frontend")
+            .unwrap_err()
+            .to_string(),
+        "context 'frontend' already exists in the config"
+    );
+    assert_eq!(
+        config
+            .rename_context("missing", "other")
+            .unwrap_err()
+            .to_string(),
+        "context 'missing' was not found in the config"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_switches_the_current_context() -> Result<()> {
+    let mut config = load_config()?;
+
+    config.use_context("exp-scratch")?;
+    assert_eq!(config.get_current_context().unwrap().name, "exp-scratch");
+
+    assert!(config.use_context("missing").is_err());
+    assert_eq!(config.current_context.as_deref(), Some("exp-scratch"));
+
+    Ok(())
+}
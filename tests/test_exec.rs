@@ -0,0 +1,389 @@
+use kube_conf::errors::*;
+use kube_conf::exec::{ExecConfig, ExecCredentialCache, ExecEnvVar, InteractiveMode};
+use kube_conf::Config;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn load_exec_config() -> Result<Config> {
+    let path = format!("{}/tests/config-exec.yml", env!("CARGO_MANIFEST_DIR"));
+    Config::load(&path)
+}
+
+#[test]
+pub fn it_parses_the_exec_config() -> Result<()> {
+    let config = load_exec_config()?;
+    let user = config.users.first().unwrap();
+    assert!(
+        user.exec.is_some(),
+        "Expected the user to have an exec config"
+    );
+
+    let exec = user.exec.as_ref().unwrap();
+    assert_eq!(
+        exec.command, "sh",
+        "Exec config did not have the expected command"
+    );
+    assert_eq!(
+        exec.args.as_ref().unwrap(),
+        &vec!["tests/exec-plugin.sh".to_string(), "exec-user".to_string()],
+        "Exec config did not have the expected args"
+    );
+    assert_eq!(
+        exec.env.as_ref().unwrap().first().unwrap().name,
+        "STUB_VARIABLE",
+        "Exec config did not have the expected env"
+    );
+    assert_eq!(
+        exec.install_hint.as_ref().unwrap(),
+        "Install the stub plugin",
+        "Exec config did not have the expected install hint"
+    );
+    assert!(
+        exec.provide_cluster_info,
+        "Expected the exec config to provide cluster info"
+    );
+    assert_eq!(
+        exec.interactive_mode,
+        Some(InteractiveMode::Never),
+        "Exec config did not have the expected interactive mode"
+    );
+
+    let value: serde_yaml::Value = serde_yaml::from_str(&config.to_yaml_string()?)?;
+    assert_eq!(
+        value["users"][0]["user"]["exec"]["installHint"].as_str(),
+        Some("Install the stub plugin"),
+        "Expected the exec config to be written back"
+    );
+    assert_eq!(
+        value["users"][0]["user"]["exec"]["futureKey"].as_str(),
+        Some("keepme"),
+        "Expected the unknown exec keys to be written back"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_runs_the_exec_plugin() -> Result<()> {
+    let config = load_exec_config()?;
+    let context = config.get_current_context().unwrap();
+    let user = context.get_user(&config).unwrap();
+    let cluster = context.get_cluster(&config).unwrap();
+
+    let dir = tempfile::tempdir()?;
+    let info_path = dir.path().join("exec-info.json");
+    let mut exec = user.exec.unwrap();
+    exec.env
+        .as_mut()
+        .unwrap()
+        .push(kube_conf::exec::ExecEnvVar {
+            name: "EXEC_INFO_OUT".to_string(),
+            value: info_path.to_str().unwrap().to_string(),
+        });
+
+    let credential = exec.run_with_cluster(Some(&cluster))?;
+    assert_eq!(
+        credential.token(),
+        Some("token-for-exec-user"),
+        "Exec plugin did not return the expected token"
+    );
+    assert_eq!(
+        credential
+            .status
+            .as_ref()
+            .unwrap()
+            .expiration_timestamp
+            .as_deref(),
+        Some("2099-01-01T00:00:00Z"),
+        "Exec plugin did not return the expected expiration"
+    );
+
+    let info: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&info_path)?)?;
+    assert_eq!(info["kind"], "ExecCredential");
+    assert_eq!(info["apiVersion"], "client.authentication.k8s.io/v1");
+    assert_eq!(info["spec"]["interactive"], false);
+    assert_eq!(
+        info["spec"]["cluster"]["server"], "https://1.2.3.4",
+        "Expected the cluster info to be passed to the plugin"
+    );
+    assert_eq!(
+        info["spec"]["cluster"]["certificate-authority-data"],
+        "ZmFrZS1jYS1kYXRh"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_passes_the_full_cluster_info_to_the_plugin() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("ca.crt"), "fake-ca-file")?;
+    let config_path = dir.path().join("config");
+    std::fs::write(
+        &config_path,
+        r#"apiVersion: v1
+clusters:
+- cluster:
+    certificate-authority: ca.crt
+    disable-compression: true
+    extensions:
+    - extension:
+        audience: kubernetes
+      name: client.authentication.k8s.io/exec
+    proxy-url: http://proxy:3128
+    server: https://1.2.3.4
+    tls-server-name: kubernetes.internal
+  name: development
+contexts: []
+users: []
+"#,
+    )?;
+    let config = Config::load(&config_path)?;
+    let cluster = config.cluster("development").unwrap();
+
+    let info_path = dir.path().join("exec-info.json");
+    let exec = ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec!["tests/exec-plugin.sh".to_string(), "info".to_string()]),
+        env: Some(vec![ExecEnvVar {
+            name: "EXEC_INFO_OUT".to_string(),
+            value: info_path.to_str().unwrap().to_string(),
+        }]),
+        install_hint: None,
+        provide_cluster_info: true,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    };
+    exec.run_with_cluster(Some(cluster))?;
+
+    let info: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&info_path)?)?;
+    assert_eq!(
+        info["spec"]["cluster"],
+        serde_json::json!({
+            "server": "https://1.2.3.4",
+            "tls-server-name": "kubernetes.internal",
+            "certificate-authority-data": "ZmFrZS1jYS1maWxl",
+            "proxy-url": "http://proxy:3128",
+            "disable-compression": true,
+            "config": { "audience": "kubernetes" },
+        }),
+        "Expected the CA file and the other cluster settings to be passed to the plugin"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_when_the_exec_plugin_fails() {
+    let exec = ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec!["tests/exec-plugin.sh".to_string(), "fail".to_string()]),
+        env: None,
+        install_hint: None,
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    };
+
+    let result = exec.run();
+    assert!(
+        result.is_err(),
+        "Expected return to be an error, but was success"
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "exec plugin 'sh' failed: plugin exited with exit status: 1"
+    );
+}
+
+#[test]
+pub fn it_returns_the_install_hint_when_the_command_is_missing() {
+    let exec = ExecConfig {
+        api_version: None,
+        command: "/path/that/does/not/exist".to_string(),
+        args: None,
+        env: None,
+        install_hint: Some("Install the stub plugin".to_string()),
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    };
+
+    let result = exec.run();
+    assert!(
+        result.is_err(),
+        "Expected return to be an error, but was success"
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "exec plugin '/path/that/does/not/exist' failed: Install the stub plugin"
+    );
+}
+
+fn counting_exec(runs_path: &Path, expiration: &str) -> ExecConfig {
+    ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec![
+            "tests/exec-plugin.sh".to_string(),
+            "cached".to_string(),
+        ]),
+        env: Some(vec![
+            ExecEnvVar {
+                name: "RUNS_OUT".to_string(),
+                value: runs_path.to_str().unwrap().to_string(),
+            },
+            ExecEnvVar {
+                name: "EXPIRATION".to_string(),
+                value: expiration.to_string(),
+            },
+        ]),
+        install_hint: None,
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    }
+}
+
+fn count_runs(runs_path: &Path) -> usize {
+    std::fs::read_to_string(runs_path)
+        .map(|runs| runs.lines().count())
+        .unwrap_or_default()
+}
+
+#[test]
+pub fn it_reuses_cached_credentials_until_they_expire() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runs_path = dir.path().join("runs");
+    let exec = counting_exec(&runs_path, "2099-01-01T00:00:00Z");
+    let cache = ExecCredentialCache::new();
+
+    let first = cache.get(&exec)?;
+    let second = cache.get(&exec)?;
+    assert_eq!(
+        first, second,
+        "Expected the cached credential to be returned"
+    );
+    assert_eq!(count_runs(&runs_path), 1, "Expected the plugin to run once");
+
+    cache.invalidate(&exec, None);
+    cache.get(&exec)?;
+    assert_eq!(
+        count_runs(&runs_path),
+        2,
+        "Expected the plugin to run again after invalidating"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_refreshes_credentials_shortly_before_they_expire() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runs_path = dir.path().join("runs");
+    let expiration = (chrono::Utc::now() + chrono::Duration::seconds(20)).to_rfc3339();
+    let exec = counting_exec(&runs_path, &expiration);
+
+    let cache = ExecCredentialCache::new().with_refresh_before(Duration::from_secs(10));
+    cache.get(&exec)?;
+    cache.get(&exec)?;
+    assert_eq!(
+        count_runs(&runs_path),
+        1,
+        "Expected the credential to be reused outside the refresh window"
+    );
+
+    let cache = ExecCredentialCache::new().with_refresh_before(Duration::from_secs(60));
+    cache.get(&exec)?;
+    cache.get(&exec)?;
+    assert_eq!(
+        count_runs(&runs_path),
+        3,
+        "Expected the credential to be refreshed inside the refresh window"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_shares_cached_credentials_through_a_directory() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runs_path = dir.path().join("runs");
+    let cache_dir = dir.path().join("cache");
+    let exec = counting_exec(&runs_path, "2099-01-01T00:00:00Z");
+
+    let credential = ExecCredentialCache::new()
+        .with_directory(&cache_dir)
+        .get(&exec)?;
+    let cached = ExecCredentialCache::new()
+        .with_directory(&cache_dir)
+        .get(&exec)?;
+
+    assert_eq!(credential, cached, "Expected the credential from disk");
+    assert_eq!(
+        count_runs(&runs_path),
+        1,
+        "Expected the second cache to read the credential from disk"
+    );
+    assert_eq!(
+        std::fs::read_dir(&cache_dir)?.count(),
+        1,
+        "Expected a single credential file in the cache directory"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_keeps_credentials_apart_by_environment() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runs_path = dir.path().join("runs");
+    let exec = counting_exec(&runs_path, "2099-01-01T00:00:00Z");
+    let cache = ExecCredentialCache::new().with_env_var("KUBE_CONF_TEST_PROFILE");
+
+    std::env::set_var("KUBE_CONF_TEST_PROFILE", "first");
+    cache.get(&exec)?;
+    cache.get(&exec)?;
+    std::env::set_var("KUBE_CONF_TEST_PROFILE", "second");
+    cache.get(&exec)?;
+    std::env::remove_var("KUBE_CONF_TEST_PROFILE");
+
+    assert_eq!(
+        count_runs(&runs_path),
+        2,
+        "Expected the plugin to run again for another profile"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_does_not_wait_for_other_plugins() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut slow = counting_exec(&dir.path().join("slow-runs"), "2099-01-01T00:00:00Z");
+    slow.env.as_mut().unwrap().push(ExecEnvVar {
+        name: "SLEEP".to_string(),
+        value: "2".to_string(),
+    });
+    let fast = counting_exec(&dir.path().join("fast-runs"), "2099-01-01T00:00:00Z");
+    let cache = Arc::new(ExecCredentialCache::new());
+
+    let waiting = {
+        let cache = cache.clone();
+        thread::spawn(move || cache.get(&slow).map(|_| ()))
+    };
+    thread::sleep(Duration::from_millis(200));
+    let started = Instant::now();
+    cache.get(&fast)?;
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "Expected the fast plugin not to wait for the slow one"
+    );
+    waiting.join().unwrap()?;
+
+    Ok(())
+}
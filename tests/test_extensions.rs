@@ -0,0 +1,134 @@
+use kube_conf::errors::*;
+use kube_conf::extension::Extensions;
+use kube_conf::Config;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MinikubeInfo {
+    last_update: String,
+    provider: String,
+    version: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Ownership {
+    owner: String,
+}
+
+fn load_config() -> Result<Config> {
+    Config::load(format!(
+        "{}/tests/config-extensions.yml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+}
+
+#[test]
+pub fn it_decodes_extensions_by_name() -> Result<()> {
+    let conf = load_config()?;
+
+    let info: MinikubeInfo = conf.clusters[0].extensions.get("cluster_info")?.unwrap();
+    assert_eq!(
+        info,
+        MinikubeInfo {
+            last_update: "Mon, 02 Jan 2023 15:04:05 UTC".to_string(),
+            provider: "minikube.sigs.k8s.io".to_string(),
+            version: "v1.32.0".to_string(),
+        }
+    );
+
+    let info: Option<MinikubeInfo> = conf.contexts[0].extensions.get("context_info")?;
+    assert!(info.is_some());
+
+    let ownership: Ownership = conf.extensions.get("example.com/ownership")?.unwrap();
+    assert_eq!(ownership.owner, "platform-team");
+
+    let list: Vec<String> = conf.extensions.get("example.com/list")?.unwrap();
+    assert_eq!(list, vec!["first", "second"]);
+
+    assert_eq!(
+        conf.users[0].extensions.get_value("user_info").unwrap()["issued-by"],
+        serde_yaml::Value::String("minikube".to_string())
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_none_for_missing_extensions() -> Result<()> {
+    let conf = load_config()?;
+
+    let missing: Option<Ownership> = conf.extensions.get("missing")?;
+    assert!(missing.is_none());
+    assert!(conf.users[0].extra.is_empty(), "Expected no unknown keys");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_when_an_extension_does_not_decode() -> Result<()> {
+    let conf = load_config()?;
+
+    let result: Result<Option<Ownership>> = conf.extensions.get("example.com/list");
+    assert!(
+        result.is_err(),
+        "Expected a list to not decode as a mapping"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_extensions_back_in_order() -> Result<()> {
+    let path = format!("{}/tests/config-extensions.yml", env!("CARGO_MANIFEST_DIR"));
+    let conf = Config::load(&path)?;
+    let written: Config = serde_yaml::from_str(&conf.to_yaml_string()?)?;
+
+    assert_eq!(written.extensions, conf.extensions);
+    assert_eq!(
+        written
+            .extensions
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["example.com/ownership", "example.com/list"]
+    );
+    assert_eq!(written.clusters[0].extensions, conf.clusters[0].extensions);
+    assert_eq!(written.contexts[0].extensions, conf.contexts[0].extensions);
+    assert_eq!(written.users[0].extensions, conf.users[0].extensions);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_inserts_and_removes_extensions() -> Result<()> {
+    let mut extensions = Extensions::new();
+    extensions.insert(
+        "example.com/ownership",
+        &Ownership {
+            owner: "first-team".to_string(),
+        },
+    )?;
+    extensions.insert("example.com/other", &vec![1, 2])?;
+    extensions.insert(
+        "example.com/ownership",
+        &Ownership {
+            owner: "second-team".to_string(),
+        },
+    )?;
+
+    assert_eq!(extensions.len(), 2);
+    assert_eq!(
+        extensions.iter().next().unwrap().name,
+        "example.com/ownership",
+        "Expected the replaced extension to keep its place"
+    );
+    let ownership: Ownership = extensions.get("example.com/ownership")?.unwrap();
+    assert_eq!(ownership.owner, "second-team");
+
+    assert!(extensions.remove("example.com/other").is_some());
+    assert!(extensions.remove("example.com/other").is_none());
+    assert_eq!(extensions.len(), 1);
+
+    Ok(())
+}
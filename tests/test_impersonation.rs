@@ -0,0 +1,101 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use std::collections::BTreeMap;
+
+fn load_config() -> Result<Config> {
+    Config::load(format!(
+        "{}/tests/config-impersonation.yml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+}
+
+#[test]
+pub fn it_parses_the_impersonation_keys() -> Result<()> {
+    let conf = load_config()?;
+    let user = &conf.users[0];
+
+    assert_eq!(user.impersonate.as_deref(), Some("jane"));
+    assert_eq!(user.impersonate_uid.as_deref(), Some("1234"));
+    assert_eq!(
+        user.impersonate_groups,
+        Some(vec![
+            "developers".to_string(),
+            "system:authenticated".to_string()
+        ])
+    );
+
+    let mut extra = BTreeMap::new();
+    extra.insert("reason".to_string(), vec!["audit".to_string()]);
+    extra.insert(
+        "scopes.example.com/project".to_string(),
+        vec!["frontend".to_string(), "backend".to_string()],
+    );
+    assert_eq!(user.impersonate_user_extra, Some(extra));
+    assert!(user.extra.is_empty(), "Expected no unknown keys");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_resolves_the_token_file() -> Result<()> {
+    let conf = load_config()?;
+
+    assert_eq!(
+        conf.users[0].token_file,
+        Some(format!("{}/tests/auditor-token", env!("CARGO_MANIFEST_DIR")).into())
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_renders_the_impersonation_headers() -> Result<()> {
+    let conf = load_config()?;
+    let impersonation = conf.users[0].impersonation().unwrap();
+
+    let headers: Vec<(&str, &str)> = vec![
+        ("Impersonate-User", "jane"),
+        ("Impersonate-Uid", "1234"),
+        ("Impersonate-Group", "developers"),
+        ("Impersonate-Group", "system:authenticated"),
+        ("Impersonate-Extra-reason", "audit"),
+        ("Impersonate-Extra-scopes.example.com%2Fproject", "frontend"),
+        ("Impersonate-Extra-scopes.example.com%2Fproject", "backend"),
+    ];
+    assert_eq!(
+        impersonation.headers(),
+        headers
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_does_not_impersonate_without_a_user() -> Result<()> {
+    let mut conf = load_config()?;
+    conf.users[0].impersonate = None;
+
+    assert!(conf.users[0].impersonation().is_none());
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_the_impersonation_keys_back() -> Result<()> {
+    let conf = load_config()?;
+    let written: Config = serde_yaml::from_str(&conf.to_yaml_string()?)?;
+
+    let (user, written_user) = (&conf.users[0], &written.users[0]);
+    assert_eq!(written_user.impersonate, user.impersonate);
+    assert_eq!(written_user.impersonate_uid, user.impersonate_uid);
+    assert_eq!(written_user.impersonate_groups, user.impersonate_groups);
+    assert_eq!(
+        written_user.impersonate_user_extra,
+        user.impersonate_user_extra
+    );
+
+    Ok(())
+}
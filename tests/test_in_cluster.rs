@@ -0,0 +1,153 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use std::env;
+use std::fs::write;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// The tests change the environment of the process, so they must not run at
+/// the same time
+static ENV: Mutex<()> = Mutex::new(());
+
+fn lock_env(host: Option<&str>, port: Option<&str>) -> MutexGuard<'static, ()> {
+    let guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match host {
+        Some(host) => env::set_var("KUBERNETES_SERVICE_HOST", host),
+        None => env::remove_var("KUBERNETES_SERVICE_HOST"),
+    }
+    match port {
+        Some(port) => env::set_var("KUBERNETES_SERVICE_PORT", port),
+        None => env::remove_var("KUBERNETES_SERVICE_PORT"),
+    }
+    guard
+}
+
+fn write_service_account(root: &Path) -> Result<()> {
+    write(root.join("token"), "service-account-token\n")?;
+    write(root.join("ca.crt"), "fake-ca-data")?;
+    write(root.join("namespace"), "some-namespace")?;
+    Ok(())
+}
+
+#[test]
+pub fn it_loads_the_in_cluster_config() -> Result<()> {
+    let _env = lock_env(Some("10.0.0.1"), Some("443"));
+    let dir = tempfile::tempdir()?;
+    write_service_account(dir.path())?;
+
+    let conf = Config::in_cluster_with_root(dir.path())?;
+
+    assert_eq!(conf.current_context.as_deref(), Some("in-cluster"));
+    assert_eq!(conf.clusters[0].server, "https://10.0.0.1:443");
+    assert_eq!(
+        conf.clusters[0].certificate_authority,
+        Some(dir.path().join("ca.crt"))
+    );
+    assert_eq!(
+        conf.contexts[0].namespace.as_deref(),
+        Some("some-namespace")
+    );
+    assert_eq!(
+        conf.users[0].token.as_deref(),
+        Some("service-account-token")
+    );
+    assert_eq!(conf.users[0].token_file, Some(dir.path().join("token")));
+
+    let context = conf.get_current_context().unwrap();
+    assert_eq!(context.get_cluster(&conf).unwrap().name, "in-cluster");
+    assert_eq!(context.get_user(&conf).unwrap().name, "in-cluster");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_puts_ipv6_hosts_in_brackets() -> Result<()> {
+    let _env = lock_env(Some("fd00::1"), Some("6443"));
+    let dir = tempfile::tempdir()?;
+    write_service_account(dir.path())?;
+
+    let conf = Config::in_cluster_with_root(dir.path())?;
+    assert_eq!(conf.clusters[0].server, "https://[fd00::1]:6443");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_outside_of_a_cluster() -> Result<()> {
+    let _env = lock_env(None, None);
+    let dir = tempfile::tempdir()?;
+    write_service_account(dir.path())?;
+
+    assert_eq!(
+        Config::in_cluster_with_root(dir.path())
+            .unwrap_err()
+            .to_string(),
+        "could not load in-cluster config: KUBERNETES_SERVICE_HOST is not set"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_without_a_token() -> Result<()> {
+    let _env = lock_env(Some("10.0.0.1"), Some("443"));
+    let dir = tempfile::tempdir()?;
+
+    let result = Config::in_cluster_with_root(dir.path());
+    match result {
+        Err(Error(ErrorKind::NotInCluster(_), _)) => {}
+        other => panic!("Expected a NotInCluster error, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn it_infers_the_kubeconfig_first() -> Result<()> {
+    let _env = lock_env(Some("10.0.0.1"), Some("443"));
+    env::set_var(
+        "KUBECONFIG",
+        format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")),
+    );
+
+    let conf = Config::infer()?;
+    assert_eq!(conf.current_context.as_deref(), Some("dev-frontend"));
+
+    Ok(())
+}
+
+#[test]
+pub fn it_infers_the_in_cluster_config_without_a_kubeconfig() {
+    let _env = lock_env(Some("10.0.0.1"), Some("443"));
+    env::set_var("KUBECONFIG", "/path/that/does/not/exist");
+
+    // The service account is not mounted here, so getting the in-cluster
+    // error shows that it was tried
+    match Config::infer() {
+        Err(Error(ErrorKind::NotInCluster(_), _)) => {}
+        other => panic!("Expected a NotInCluster error, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn it_infers_the_in_cluster_config_without_home_or_kubeconfig() {
+    let _env = lock_env(Some("10.0.0.1"), Some("443"));
+    let home = env::var_os("HOME");
+    env::remove_var("HOME");
+    env::remove_var("KUBECONFIG");
+
+    let default = Config::load_default();
+    let inferred = Config::infer();
+    if let Some(home) = home {
+        env::set_var("HOME", home);
+    }
+
+    match default {
+        Err(Error(ErrorKind::NoConfigLocation, _)) => {}
+        other => panic!("Expected a NoConfigLocation error, got {:?}", other),
+    }
+    match inferred {
+        Err(Error(ErrorKind::NotInCluster(_), _)) => {}
+        other => panic!("Expected a NotInCluster error, got {:?}", other),
+    }
+}
@@ -0,0 +1,209 @@
+#![cfg(feature = "native-tls")]
+
+mod common;
+
+use common::{write_config, Pki};
+use kube_conf::errors::*;
+use native_tls::{Identity, TlsAcceptor, TlsConnector};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+/// Starts a TLS server using the server certificate of `pki`. The server
+/// answers "world" to "hello".
+fn start_server(pki: &Pki) -> String {
+    let identity = Identity::from_pkcs8(
+        pki.server_cert_pem.as_bytes(),
+        pki.server_key_pem.as_bytes(),
+    )
+    .unwrap();
+    let acceptor = TlsAcceptor::new(identity).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("https://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Ok(mut stream) = acceptor.accept(stream.unwrap()) {
+                let mut request = [0; 5];
+                if stream.read_exact(&mut request).is_ok() && &request == b"hello" {
+                    let _ = stream.write_all(b"world");
+                    let _ = stream.flush();
+                }
+            }
+        }
+    });
+
+    address
+}
+
+fn say_hello(server: &str, connector: TlsConnector) -> std::result::Result<String, String> {
+    let address = server.trim_start_matches("https://");
+    let stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+    let mut stream = connector
+        .connect("localhost", stream)
+        .map_err(|e| e.to_string())?;
+    stream.write_all(b"hello").map_err(|e| e.to_string())?;
+    let mut response = [0; 5];
+    stream
+        .read_exact(&mut response)
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&response).to_string())
+}
+
+#[test]
+pub fn it_connects_with_the_cluster_ca() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+    let context = config.get_current_context().unwrap();
+    let connector = context.native_tls_connector(&config)?;
+
+    assert_eq!(
+        say_hello(&server, connector).unwrap(),
+        "world",
+        "Expected the server to answer over the TLS connection"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_rejects_a_server_signed_by_another_ca() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+    let other = Pki::generate();
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&other.ca_pem),
+        false,
+    )?;
+    let context = config.get_current_context().unwrap();
+    let connector = context.native_tls_connector(&config)?;
+
+    assert!(
+        say_hello(&server, connector).is_err(),
+        "Expected the handshake to fail for an untrusted server"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_skips_verification_when_insecure() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(&dir.path().join("config"), &server, &pki, None, true)?;
+    let context = config.get_current_context().unwrap();
+    let connector = context.native_tls_connector(&config)?;
+
+    assert_eq!(
+        say_hello(&server, connector).unwrap(),
+        "world",
+        "Expected the connection to succeed without verifying the server"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_accepts_rsa_and_ec_client_keys() -> Result<()> {
+    let pki = Pki::generate();
+    let dir = tempfile::tempdir()?;
+    let mut config = write_config(
+        &dir.path().join("config"),
+        "https://localhost",
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+
+    for name in &["client-rsa", "client-ec"] {
+        let fixture = |extension| {
+            PathBuf::from(format!(
+                "{}/tests/{}.{}",
+                env!("CARGO_MANIFEST_DIR"),
+                name,
+                extension
+            ))
+        };
+        let user = config.users.first_mut().unwrap();
+        user.client_certificate_data = None;
+        user.client_key_data = None;
+        user.client_certificate = Some(fixture("crt"));
+        user.client_key = Some(fixture("key"));
+
+        let context = config.get_current_context().unwrap();
+        assert!(
+            context.native_tls_connector(&config).is_ok(),
+            "Expected the {} key to be accepted as a client identity",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_for_malformed_client_keys() -> Result<()> {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    let pki = Pki::generate();
+    let dir = tempfile::tempdir()?;
+    let mut config = write_config(
+        &dir.path().join("config"),
+        "https://localhost",
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+
+    let malformed: &[(&str, &[u8])] = &[
+        // A length that does not fit in the address space
+        (
+            "EC PRIVATE KEY",
+            &[0x30, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ),
+        // A length running past the end of the data
+        (
+            "EC PRIVATE KEY",
+            &[0x30, 0x84, 0xff, 0xff, 0xff, 0xf0, 0x02, 0x01, 0x01],
+        ),
+        ("EC PRIVATE KEY", &[0x30, 0x03, 0x02, 0x01, 0x01]),
+        ("RSA PRIVATE KEY", &[0x30, 0x82, 0x01]),
+        ("PRIVATE KEY", &[]),
+    ];
+    for (label, der) in malformed {
+        let key = format!(
+            "-----BEGIN {}-----\n{}\n-----END {}-----\n",
+            label,
+            STANDARD.encode(der),
+            label
+        );
+        config.users.first_mut().unwrap().client_key_data = Some(STANDARD.encode(key));
+
+        let context = config.get_current_context().unwrap();
+        assert!(
+            context.native_tls_connector(&config).is_err(),
+            "Expected the malformed {} to be rejected",
+            label
+        );
+    }
+
+    Ok(())
+}
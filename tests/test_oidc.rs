@@ -0,0 +1,243 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use kube_conf::auth_provider::OidcConfig;
+
+fn jwt_expiring_at(exp: u64) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+    let claims = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"developer","exp":{}}}"#, exp));
+    format!("{}.{}.signature", header, claims)
+}
+
+#[test]
+pub fn it_checks_if_the_id_token_expired() {
+    let mut oidc = OidcConfig {
+        id_token: Some(jwt_expiring_at(4_000_000_000)),
+        ..OidcConfig::default()
+    };
+    assert!(
+        !oidc.id_token_expired(),
+        "Expected a token expiring in the future to be valid"
+    );
+
+    oidc.id_token = Some(jwt_expiring_at(1_000));
+    assert!(
+        oidc.id_token_expired(),
+        "Expected a token expiring in the past to be expired"
+    );
+
+    oidc.id_token = Some("not-a-jwt".to_string());
+    assert!(
+        oidc.id_token_expired(),
+        "Expected a token that can not be parsed to be expired"
+    );
+
+    oidc.id_token = None;
+    assert!(
+        oidc.id_token_expired(),
+        "Expected a missing token to be expired"
+    );
+}
+
+#[cfg(feature = "oidc")]
+mod refresh {
+    use super::jwt_expiring_at;
+    use kube_conf::auth_provider::AuthProvider;
+    use kube_conf::errors::*;
+    use kube_conf::Config;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+
+    /// Serves the discovery document and the token endpoint of a fake
+    /// identity provider, sending the bodies of the token requests back
+    fn mock_issuer(new_id_token: String) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = channel();
+
+        let token_endpoint = format!("{}/token", issuer);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    let header = header.to_lowercase();
+                    if let Some(length) = header.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let response = if request_line.contains("/.well-known/openid-configuration") {
+                    format!(r#"{{"token_endpoint":"{}"}}"#, token_endpoint)
+                } else {
+                    sender.send(String::from_utf8(body).unwrap()).unwrap();
+                    format!(
+                        r#"{{"id_token":"{}","refresh_token":"new-refresh-token"}}"#,
+                        new_id_token
+                    )
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+
+        (issuer, receiver)
+    }
+
+    fn write_config(path: &std::path::Path, issuer: &str, id_token: &str) -> Result<()> {
+        let config = format!(
+            r#"apiVersion: v1
+clusters:
+- cluster:
+    server: https://1.2.3.4
+  name: development
+contexts:
+- context:
+    cluster: development
+    user: oidc-user
+  name: dev
+current-context: dev
+kind: Config
+users:
+- name: oidc-user
+  user:
+    auth-provider:
+      config:
+        client-id: kubernetes
+        client-secret: some-secret
+        id-token: {}
+        idp-issuer-url: {}
+        refresh-token: old-refresh-token
+      name: oidc
+"#,
+            id_token, issuer
+        );
+        std::fs::write(path, config)?;
+        Ok(())
+    }
+
+    #[test]
+    pub fn it_refreshes_expired_tokens_and_writes_them_back() -> Result<()> {
+        let new_id_token = jwt_expiring_at(4_000_000_000);
+        let (issuer, requests) = mock_issuer(new_id_token.clone());
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config");
+        write_config(&path, &issuer, &jwt_expiring_at(1_000))?;
+
+        let mut config = Config::load(&path)?;
+        assert!(
+            config.refresh_oidc_token("oidc-user")?,
+            "Expected the expired token to be refreshed"
+        );
+
+        let body = requests.recv().unwrap();
+        assert!(body.contains("grant_type=refresh_token"), "{}", body);
+        assert!(body.contains("refresh_token=old-refresh-token"), "{}", body);
+        assert!(body.contains("client_id=kubernetes"), "{}", body);
+
+        let saved = Config::load(&path)?;
+        match saved.users.first().unwrap().auth_provider.as_ref() {
+            Some(AuthProvider::Oidc(oidc)) => {
+                assert_eq!(
+                    oidc.id_token.as_ref(),
+                    Some(&new_id_token),
+                    "Expected the new id-token to be written back"
+                );
+                assert_eq!(
+                    oidc.refresh_token.as_deref(),
+                    Some("new-refresh-token"),
+                    "Expected the new refresh-token to be written back"
+                );
+            }
+            other => panic!("Expected an oidc auth-provider, got {:?}", other),
+        }
+
+        assert!(
+            !config.refresh_oidc_token("oidc-user")?,
+            "Expected a valid token not to be refreshed"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn it_writes_refreshed_tokens_to_the_file_of_the_user() -> Result<()> {
+        let new_id_token = jwt_expiring_at(4_000_000_000);
+        let (issuer, _requests) = mock_issuer(new_id_token.clone());
+
+        let dir = tempfile::tempdir()?;
+        let first = dir.path().join("config");
+        let second = dir.path().join("config-oidc");
+        std::fs::write(
+            &first,
+            std::fs::read_to_string(format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")))?,
+        )?;
+        write_config(&second, &issuer, &jwt_expiring_at(1_000))?;
+        let before = std::fs::read_to_string(&first)?;
+
+        let mut config = Config::load_all([&first, &second])?;
+        assert!(config.refresh_oidc_token("oidc-user")?);
+
+        assert_eq!(std::fs::read_to_string(&first)?, before);
+        let saved = Config::load(&second)?;
+        match saved.user("oidc-user").unwrap().auth_provider.as_ref() {
+            Some(AuthProvider::Oidc(oidc)) => assert_eq!(
+                oidc.id_token.as_ref(),
+                Some(&new_id_token),
+                "Expected the new id-token to be written to the second file"
+            ),
+            other => panic!("Expected an oidc auth-provider, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn it_writes_only_the_refreshed_tokens() -> Result<()> {
+        let new_id_token = jwt_expiring_at(4_000_000_000);
+        let (issuer, _requests) = mock_issuer(new_id_token.clone());
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config");
+        write_config(&path, &issuer, &jwt_expiring_at(1_000))?;
+
+        let mut config = Config::load(&path)?;
+        config.remove_cluster("development");
+        config.current_context = None;
+        assert!(config.refresh_oidc_token("oidc-user")?);
+
+        let saved = Config::load(&path)?;
+        assert!(
+            saved.cluster("development").is_some(),
+            "Expected the cluster removed in memory to stay in the file"
+        );
+        assert_eq!(saved.current_context.as_deref(), Some("dev"));
+        match saved.user("oidc-user").unwrap().auth_provider.as_ref() {
+            Some(AuthProvider::Oidc(oidc)) => {
+                assert_eq!(oidc.id_token.as_ref(), Some(&new_id_token));
+                assert_eq!(oidc.refresh_token.as_deref(), Some("new-refresh-token"));
+            }
+            other => panic!("Expected an oidc auth-provider, got {:?}", other),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+const SECOND: &str = r#"apiVersion: v1
+clusters:
+- cluster:
+    server: https://9.9.9.9
+  name: staging
+contexts:
+- context:
+    cluster: staging
+    user: stager
+  name: staging
+current-context: staging
+kind: Config
+users:
+- name: stager
+  user:
+    token: abc
+"#;
+
+/// Copies the test config and a second file into a temporary directory
+fn write_files(dir: &Path) -> Result<(PathBuf, PathBuf)> {
+    let first = dir.join("config");
+    let second = dir.join("config-2");
+    write(
+        &first,
+        read_to_string(format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")))?,
+    )?;
+    write(&second, SECOND)?;
+    Ok((first, second))
+}
+
+#[test]
+pub fn it_remembers_where_entries_came_from() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (first, second) = write_files(dir.path())?;
+    let config = Config::load_all([&first, &second])?;
+
+    assert_eq!(config.sources(), &[first.clone(), second.clone()][..]);
+    assert_eq!(config.cluster_source("development"), Some(first.as_path()));
+    assert_eq!(config.cluster_source("staging"), Some(second.as_path()));
+    assert_eq!(config.context_source("staging"), Some(second.as_path()));
+    assert_eq!(config.user_source("stager"), Some(second.as_path()));
+    assert_eq!(config.user_source("missing"), None);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_changes_to_the_file_they_came_from() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (first, second) = write_files(dir.path())?;
+    let before = read_to_string(&first)?;
+    let mut config = Config::load_all([&first, &second])?;
+
+    let mut cluster = config.cluster("staging").unwrap().clone();
+    cluster.server = "https://8.8.8.8".to_string();
+    config.replace_cluster(cluster);
+    config.persist()?;
+
+    assert_eq!(
+        read_to_string(&first)?,
+        before,
+        "Expected the untouched file to stay the same"
+    );
+    let written = Config::load(&second)?;
+    assert_eq!(
+        written.cluster("staging").unwrap().server,
+        "https://8.8.8.8"
+    );
+    assert_eq!(
+        written.current_context.as_deref(),
+        Some("staging"),
+        "Expected the current context of the second file to be kept"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_new_entries_and_current_context_to_the_first_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (first, second) = write_files(dir.path())?;
+    let before = read_to_string(&second)?;
+    let mut config = Config::load_all([&first, &second])?;
+
+    let mut context = config.context("staging").unwrap().clone();
+    context.name = "new-context".to_string();
+    config.replace_context(context);
+    config.use_context("new-context")?;
+    config.persist()?;
+
+    assert_eq!(read_to_string(&second)?, before);
+    let written = Config::load(&first)?;
+    assert!(written.context("new-context").is_some());
+    assert_eq!(written.current_context.as_deref(), Some("new-context"));
+    assert_eq!(
+        config.context_source("new-context"),
+        Some(first.as_path()),
+        "Expected the new context to belong to the first file after persisting"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_removes_and_renames_in_the_file_they_came_from() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (first, second) = write_files(dir.path())?;
+    let mut config = Config::load_all([&first, &second])?;
+
+    config.remove_user("stager");
+    config.rename_context("staging", "renamed")?;
+    config.persist()?;
+
+    let written = Config::load(&second)?;
+    assert!(written.user("stager").is_none());
+    assert!(written.context("staging").is_none());
+    assert!(written.context("renamed").is_some());
+    let written = Config::load(&first)?;
+    assert!(written.context("renamed").is_none());
+    assert_eq!(written.users.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+pub fn it_renames_contexts_in_place() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (first, second) = write_files(dir.path())?;
+    let mut config = Config::load_all([&first, &second])?;
+
+    config.rename_context("dev-frontend", "frontend")?;
+    config.rename_context("frontend", "web")?;
+    config.persist()?;
+
+    let written = Config::load_raw(&first)?;
+    assert_eq!(
+        written
+            .contexts
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["web", "dev-storage", "exp-scratch"],
+        "Expected the renamed context to keep its place"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_when_not_loaded_from_a_file() -> Result<()> {
+    let mut config: Config = serde_yaml::from_str(
+        "apiVersion: v1\nclusters: []\ncontexts: []\nkind: Config\nusers: []\n",
+    )?;
+
+    assert_eq!(
+        config.persist().unwrap_err().to_string(),
+        "the config was not loaded from a file"
+    );
+
+    Ok(())
+}
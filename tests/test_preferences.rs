@@ -0,0 +1,73 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use serde_derive::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Limits {
+    max_retries: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Display {
+    theme: String,
+}
+
+fn load_config() -> Result<Config> {
+    Config::load(format!(
+        "{}/tests/config-preferences.yml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+}
+
+#[test]
+pub fn it_parses_the_typed_preferences() -> Result<()> {
+    let preferences = load_config()?.preferences.unwrap();
+
+    assert_eq!(preferences.colors, Some(true));
+    let display: Display = preferences.extensions.get("example.com/display")?.unwrap();
+    assert_eq!(display.theme, "dark");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_decodes_custom_preferences() -> Result<()> {
+    let preferences = load_config()?.preferences.unwrap();
+
+    assert_eq!(
+        preferences.get::<Limits>("limits")?,
+        Some(Limits { max_retries: 3 })
+    );
+    assert_eq!(preferences.get::<String>("missing")?, None);
+    assert!(
+        preferences.get::<Limits>("fooValue").is_err(),
+        "Expected a string to not decode as a mapping"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_writes_the_preferences_back() -> Result<()> {
+    let mut conf = load_config()?;
+    conf.preferences
+        .as_mut()
+        .unwrap()
+        .set("barValue", &vec!["x", "y"])?;
+
+    let written: Config = serde_yaml::from_str(&conf.to_yaml_string()?)?;
+    let preferences = written.preferences.unwrap();
+
+    assert_eq!(preferences, conf.preferences.unwrap());
+    assert_eq!(
+        preferences.get::<Vec<String>>("barValue")?,
+        Some(vec!["x".to_string(), "y".to_string()])
+    );
+    assert_eq!(
+        preferences.get::<String>("fooValue")?.as_deref(),
+        Some("abc")
+    );
+
+    Ok(())
+}
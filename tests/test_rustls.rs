@@ -0,0 +1,162 @@
+#![cfg(feature = "rustls")]
+
+mod common;
+
+use common::{write_config, Pki};
+use kube_conf::errors::*;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+/// Starts a TLS server requiring a client certificate signed by the CA of
+/// `pki`. The server answers "world" to "hello".
+fn start_server(pki: &Pki) -> String {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(pki.ca_pem.as_bytes()).unwrap())
+        .unwrap();
+    let client_verifier =
+        WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .unwrap();
+    let server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_client_cert_verifier(client_verifier)
+        .with_single_cert(
+            vec![CertificateDer::from_pem_slice(pki.server_cert_pem.as_bytes()).unwrap()],
+            PrivateKeyDer::from_pem_slice(pki.server_key_pem.as_bytes()).unwrap(),
+        )
+        .unwrap();
+    let server_config = Arc::new(server_config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("https://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = ServerConnection::new(server_config.clone()).unwrap();
+            let mut stream = StreamOwned::new(connection, stream.unwrap());
+            let mut request = [0; 5];
+            if stream.read_exact(&mut request).is_ok() && &request == b"hello" {
+                let _ = stream.write_all(b"world");
+                let _ = stream.flush();
+            }
+        }
+    });
+
+    address
+}
+
+fn say_hello(server: &str, tls_config: ClientConfig) -> std::io::Result<String> {
+    let address = server.trim_start_matches("https://");
+    let connection = ClientConnection::new(
+        Arc::new(tls_config),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let mut stream = StreamOwned::new(connection, TcpStream::connect(address)?);
+    stream.write_all(b"hello")?;
+    let mut response = [0; 5];
+    stream.read_exact(&mut response)?;
+    Ok(String::from_utf8_lossy(&response).to_string())
+}
+
+#[test]
+pub fn it_connects_with_the_cluster_ca_and_client_certificate() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+    let context = config.get_current_context().unwrap();
+    let tls_config = context.rustls_config(&config)?;
+
+    assert_eq!(
+        say_hello(&server, tls_config)?,
+        "world",
+        "Expected the server to answer over the TLS connection"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_rejects_a_server_signed_by_another_ca() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+    let other = Pki::generate();
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&other.ca_pem),
+        false,
+    )?;
+    let context = config.get_current_context().unwrap();
+    let tls_config = context.rustls_config(&config)?;
+
+    assert!(
+        say_hello(&server, tls_config).is_err(),
+        "Expected the handshake to fail for an untrusted server"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_skips_verification_when_insecure() -> Result<()> {
+    let pki = Pki::generate();
+    let server = start_server(&pki);
+
+    let dir = tempfile::tempdir()?;
+    let config = write_config(&dir.path().join("config"), &server, &pki, None, true)?;
+    let context = config.get_current_context().unwrap();
+    let tls_config = context.rustls_config(&config)?;
+
+    assert_eq!(
+        say_hello(&server, tls_config)?,
+        "world",
+        "Expected the connection to succeed without verifying the server"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_for_an_unknown_cluster() -> Result<()> {
+    let pki = Pki::generate();
+    let dir = tempfile::tempdir()?;
+    let config = write_config(
+        &dir.path().join("config"),
+        "https://localhost",
+        &pki,
+        None,
+        false,
+    )?;
+    let mut context = config.get_current_context().unwrap().clone();
+    context.cluster = "missing".to_string();
+
+    assert_eq!(
+        context.rustls_config(&config).unwrap_err().to_string(),
+        "cluster 'missing' was not found in the config"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use std::fs::{read_dir, read_to_string, remove_file, write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+fn copy_config(dir: &Path) -> Result<PathBuf> {
+    let path = dir.join("config");
+    write(
+        &path,
+        read_to_string(format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR")))?,
+    )?;
+    Ok(path)
+}
+
+#[test]
+pub fn it_keeps_the_changes_of_concurrent_writers() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = copy_config(dir.path())?;
+
+    let writers: Vec<_> = (0..8)
+        .map(|i| {
+            let path = path.clone();
+            thread::spawn(move || -> Result<()> {
+                let mut config = Config::load_raw(&path)?;
+                let mut cluster = config.cluster("scratch").unwrap().clone();
+                cluster.name = format!("writer-{}", i);
+                config.replace_cluster(cluster);
+                config.persist()
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap()?;
+    }
+
+    let config = Config::load_raw(&path)?;
+    for i in 0..8 {
+        assert!(
+            config.cluster(&format!("writer-{}", i)).is_some(),
+            "Expected the cluster of writer {} to be kept",
+            i
+        );
+    }
+    let names: Vec<_> = read_dir(dir.path())?
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["config"],
+        "Expected no lock or temporary files to be left behind"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_waits_for_the_lock_to_be_released() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = copy_config(dir.path())?;
+    let lock_path = dir.path().join("config.lock");
+    write(&lock_path, "")?;
+
+    let releaser = {
+        let lock_path = lock_path.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            remove_file(lock_path).unwrap();
+        })
+    };
+
+    let mut config = Config::load_raw(&path)?;
+    config.current_context = Some("exp-scratch".to_string());
+    config.save(&path)?;
+    releaser.join().unwrap();
+
+    assert_eq!(
+        Config::load_raw(&path)?.current_context.as_deref(),
+        Some("exp-scratch")
+    );
+    assert!(!lock_path.exists(), "Expected the lock to be released");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_returns_error_when_the_lock_is_never_released() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = copy_config(dir.path())?;
+    write(dir.path().join("config.lock"), "")?;
+    let before = read_to_string(&path)?;
+
+    let config = Config::load_raw(&path)?;
+    match config.save(&path) {
+        Err(Error(ErrorKind::ConfigLocked(_), _)) => {}
+        other => panic!("Expected the lock to time out, got {:?}", other),
+    }
+    assert_eq!(read_to_string(&path)?, before);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_keeps_the_permissions_of_the_file() -> Result<()> {
+    use std::fs::{metadata, set_permissions, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir()?;
+    let path = copy_config(dir.path())?;
+    set_permissions(&path, Permissions::from_mode(0o640))?;
+
+    let config = Config::load_raw(&path)?;
+    config.save(&path)?;
+    assert_eq!(metadata(&path)?.permissions().mode() & 0o777, 0o640);
+
+    let new_path = dir.path().join("new-config");
+    config.save(&new_path)?;
+    assert_eq!(
+        metadata(&new_path)?.permissions().mode() & 0o777,
+        0o600,
+        "Expected new files to only be readable by the owner"
+    );
+
+    Ok(())
+}
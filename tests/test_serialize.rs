@@ -0,0 +1,234 @@
+use kube_conf::errors::*;
+use kube_conf::Config;
+use serde_yaml::Value;
+use std::path::PathBuf;
+
+#[test]
+pub fn it_serializes_to_kubeconfig_yaml() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+    let yaml = config.to_yaml_string()?;
+
+    assert!(
+        !yaml.starts_with("---"),
+        "Expected no document marker in the serialized config"
+    );
+    assert!(
+        yaml.contains("current-context: dev-frontend"),
+        "Expected the current context to be serialized in kebab-case"
+    );
+    assert!(
+        yaml.contains("apiVersion: v1"),
+        "Expected the api version to be serialized in camelCase"
+    );
+
+    let value: serde_yaml::Value = serde_yaml::from_str(&yaml)?;
+    let cluster = &value["clusters"][0];
+    assert_eq!(
+        cluster["name"].as_str(),
+        Some("development"),
+        "Expected the cluster name outside of the cluster mapping"
+    );
+    assert_eq!(
+        cluster["cluster"]["server"].as_str(),
+        Some("https://1.2.3.4"),
+        "Expected the server to be nested in the cluster mapping"
+    );
+    assert!(
+        cluster["cluster"]["insecure-skip-tls-verify"].is_null(),
+        "Expected insecure-skip-tls-verify to be omitted when false"
+    );
+    assert_eq!(
+        value["contexts"][0]["context"]["namespace"].as_str(),
+        Some("frontend"),
+        "Expected the namespace to be nested in the context mapping"
+    );
+    assert_eq!(
+        value["users"][1]["user"]["password"].as_str(),
+        Some("some-password"),
+        "Expected the password to be nested in the user mapping"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_saves_and_loads_the_same_config() -> Result<()> {
+    let path = format!("{}/tests/config.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+
+    let dir = tempfile::tempdir()?;
+    let saved_path = dir.path().join("config");
+    config.save(&saved_path)?;
+    let saved = Config::load(&saved_path)?;
+
+    assert_eq!(saved.current_context, config.current_context);
+    assert_eq!(
+        saved.clusters.len(),
+        2,
+        "Expected 2 clusters in the saved config"
+    );
+    assert_eq!(
+        saved.contexts.len(),
+        3,
+        "Expected 3 contexts in the saved config"
+    );
+    assert_eq!(saved.users.len(), 2, "Expected 2 users in the saved config");
+
+    let cluster = saved.clusters.first().unwrap();
+    assert_eq!(
+        cluster.certificate_authority.as_ref().unwrap(),
+        &PathBuf::from(format!("{}/tests/fake-ca-file", env!("CARGO_MANIFEST_DIR"))),
+        "Expected the certificate authority to survive the round trip"
+    );
+    assert!(
+        saved.clusters.get(1).unwrap().insecure_skip_tls_verify,
+        "Expected the skip_tls flag to survive the round trip"
+    );
+
+    let user = saved.users.first().unwrap();
+    assert_eq!(
+        user.client_key.as_ref().unwrap(),
+        &PathBuf::from(format!(
+            "{}/tests/fake-key-file",
+            env!("CARGO_MANIFEST_DIR")
+        )),
+        "Expected the client key to survive the round trip"
+    );
+
+    assert_eq!(
+        saved.to_yaml_string()?,
+        config.to_yaml_string()?,
+        "Expected saving a loaded config to be stable"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_preserves_unknown_keys() -> Result<()> {
+    let path = format!("{}/tests/config-unknown.yml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::load(&path)?;
+
+    let cluster = config.clusters.first().unwrap();
+    assert_eq!(
+        cluster.extra.len(),
+        2,
+        "Expected the unknown cluster keys to be kept"
+    );
+    assert_eq!(
+        cluster
+            .extra
+            .get(&Value::String("x-vendor-setting".to_string()))
+            .and_then(Value::as_str),
+        Some("enabled"),
+        "Expected the unknown cluster key to keep its value"
+    );
+    assert!(
+        cluster
+            .extra
+            .get(&Value::String("server".to_string()))
+            .is_none(),
+        "Expected known keys to be left out of the unknown keys"
+    );
+
+    assert_eq!(
+        config.extra.len(),
+        1,
+        "Expected the unknown top level key to be kept"
+    );
+    assert_eq!(
+        cluster.entry_extra.len(),
+        1,
+        "Expected the unknown key next to the cluster name to be kept"
+    );
+
+    let context = config.contexts.first().unwrap();
+    assert_eq!(
+        context.extra.len(),
+        1,
+        "Expected the unknown context key to be kept"
+    );
+
+    let user = config.users.first().unwrap();
+    assert_eq!(
+        user.extra.len(),
+        1,
+        "Expected the unknown user key to be kept"
+    );
+
+    let value: Value = serde_yaml::from_str(&config.to_yaml_string()?)?;
+    assert_eq!(
+        value["clusters"][0]["cluster"]["x-vendor-limits"]["requests"].as_u64(),
+        Some(10),
+        "Expected the unknown cluster keys to be written back"
+    );
+    assert_eq!(
+        value["contexts"][0]["context"]["x-vendor-context"].as_str(),
+        Some("abc"),
+        "Expected the unknown context keys to be written back"
+    );
+    assert_eq!(
+        value["users"][0]["user"]["x-vendor-auth"]["mode"].as_str(),
+        Some("magic"),
+        "Expected the unknown user keys to be written back"
+    );
+    assert_eq!(
+        value["x-vendor-top"].as_str(),
+        Some("keep-me"),
+        "Expected the unknown top level keys to be written back"
+    );
+    assert_eq!(
+        value["clusters"][0]["x-vendor-owner"].as_str(),
+        Some("platform"),
+        "Expected the unknown keys next to the cluster name to be written back"
+    );
+    assert_eq!(
+        value["contexts"][0]["x-vendor-pinned"].as_bool(),
+        Some(true),
+        "Expected the unknown keys next to the context name to be written back"
+    );
+    assert_eq!(
+        value["users"][0]["x-vendor-source"].as_str(),
+        Some("sso"),
+        "Expected the unknown keys next to the user name to be written back"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_keeps_values_of_known_keys_that_have_the_wrong_type() -> Result<()> {
+    let yaml = "clusters:
+- cluster:
+    certificate-authority: 42
+    insecure-skip-tls-verify: \"true\"
+    server: https://1.2.3.4
+  name: development
+contexts: []
+preferences:
+  colors: \"yes\"
+users:
+- name: developer
+  user:
+    token: 123
+";
+    let config: Config = serde_yaml::from_str(yaml)?;
+    let cluster = config.clusters.first().unwrap();
+    assert!(cluster.certificate_authority.is_none());
+    assert!(!cluster.insecure_skip_tls_verify);
+
+    let value: Value = serde_yaml::from_str(&config.to_yaml_string()?)?;
+    assert_eq!(value, serde_yaml::from_str::<Value>(yaml)?);
+
+    let mut config = config;
+    config.users[0].token = Some("some-token".to_string());
+    let value: Value = serde_yaml::from_str(&config.to_yaml_string()?)?;
+    assert_eq!(
+        value["users"][0]["user"]["token"].as_str(),
+        Some("some-token"),
+        "Expected a value set on the typed field to win over the kept one"
+    );
+
+    Ok(())
+}
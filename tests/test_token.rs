@@ -0,0 +1,96 @@
+use kube_conf::errors::*;
+use kube_conf::token::TokenSource;
+use kube_conf::Config;
+use std::fs::{remove_file, write, File};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+fn set_modified(path: &PathBuf, modified: SystemTime) -> Result<()> {
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(modified)?;
+    Ok(())
+}
+
+#[test]
+pub fn it_reads_the_token_file_again_when_it_changes() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("token");
+    write(&path, "first-token\n")?;
+    set_modified(&path, SystemTime::UNIX_EPOCH)?;
+
+    let source = TokenSource::file(&path);
+    assert_eq!(source.current_token()?, "first-token");
+
+    // Same mtime, so the cached token is used within the interval
+    write(&path, "second-token\n")?;
+    set_modified(&path, SystemTime::UNIX_EPOCH)?;
+    assert_eq!(source.current_token()?, "first-token");
+
+    set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(60))?;
+    assert_eq!(source.current_token()?, "second-token");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_reads_the_token_file_again_after_the_interval() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("token");
+    write(&path, "first-token")?;
+    set_modified(&path, SystemTime::UNIX_EPOCH)?;
+
+    let source = TokenSource::file(&path).with_interval(Duration::from_millis(0));
+    assert_eq!(source.current_token()?, "first-token");
+
+    write(&path, "second-token")?;
+    set_modified(&path, SystemTime::UNIX_EPOCH)?;
+    assert_eq!(source.current_token()?, "second-token");
+
+    Ok(())
+}
+
+#[test]
+pub fn it_keeps_the_last_token_when_the_file_disappears() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("token");
+    write(&path, "first-token")?;
+
+    let source = TokenSource::file(&path);
+    assert_eq!(source.current_token()?, "first-token");
+
+    remove_file(&path)?;
+    assert_eq!(source.current_token()?, "first-token");
+
+    let unread = TokenSource::file(dir.path().join("missing"));
+    assert!(
+        unread.current_token().is_err(),
+        "Expected an error for a file that was never read"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_prefers_the_token_file_of_the_user() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("token");
+    write(&path, "file-token")?;
+
+    let mut user = Config::load("tests/config.yml")?.users.remove(0);
+    assert!(user.token_source().is_none());
+
+    user.token = Some("static-token".to_string());
+    assert_eq!(
+        user.token_source().unwrap().current_token()?,
+        "static-token"
+    );
+
+    user.token_file = Some(path.clone());
+    let source = user.token_source().unwrap();
+    assert_eq!(source.path(), Some(path.as_path()));
+    assert_eq!(source.current_token()?, "file-token");
+
+    Ok(())
+}
@@ -0,0 +1,358 @@
+#![cfg(feature = "tower")]
+
+mod common;
+
+use common::{start_https_server, write_config, Pki};
+use http::{HeaderMap, Request};
+use http_body_util::Empty;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use kube_conf::errors::*;
+use kube_conf::exec::{ExecConfig, ExecEnvVar, InteractiveMode};
+use kube_conf::http::AuthLayer;
+use kube_conf::Config;
+use std::convert::Infallible;
+use std::future::{poll_fn, ready, Ready};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A service recording the headers of the requests it gets
+#[derive(Clone, Default)]
+struct Recorder {
+    headers: Arc<Mutex<Vec<HeaderMap>>>,
+}
+
+impl Service<Request<()>> for Recorder {
+    type Response = ();
+    type Error = Infallible;
+    type Future = Ready<std::result::Result<(), Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<std::result::Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<()>) -> Self::Future {
+        self.headers.lock().unwrap().push(request.headers().clone());
+        ready(Ok(()))
+    }
+}
+
+fn load_config() -> Result<Config> {
+    Config::load("tests/config.yml")
+}
+
+async fn send(layer: &AuthLayer, recorder: &Recorder) -> Option<String> {
+    let mut service = layer.layer(recorder.clone());
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    service.call(Request::new(())).await.unwrap();
+
+    let headers = recorder.headers.lock().unwrap();
+    headers
+        .last()
+        .unwrap()
+        .get("authorization")
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+pub async fn it_adds_the_static_token() -> Result<()> {
+    let mut user = load_config()?.users.remove(0);
+    user.token = Some("some-token".to_string());
+
+    let layer = AuthLayer::new(user, None);
+    assert_eq!(
+        send(&layer, &Recorder::default()).await.as_deref(),
+        Some("Bearer some-token")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_adds_the_token_from_the_token_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let token_path = dir.path().join("token");
+    std::fs::write(&token_path, "file-token\n")?;
+
+    let mut user = load_config()?.users.remove(0);
+    user.token_file = Some(token_path);
+
+    let layer = AuthLayer::new(user, None);
+    assert_eq!(
+        send(&layer, &Recorder::default()).await.as_deref(),
+        Some("Bearer file-token")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_adds_basic_auth() -> Result<()> {
+    let mut user = load_config()?.users.remove(0);
+    user.username = Some("exp".to_string());
+    user.password = Some("some-password".to_string());
+
+    let layer = AuthLayer::new(user, None);
+    assert_eq!(
+        send(&layer, &Recorder::default()).await.as_deref(),
+        Some("Basic ZXhwOnNvbWUtcGFzc3dvcmQ=")
+    );
+
+    Ok(())
+}
+
+fn count_runs(runs_path: &Path) -> usize {
+    std::fs::read_to_string(runs_path)
+        .map(|runs| runs.lines().count())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+pub async fn it_adds_the_exec_plugin_token_and_reuses_it() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runs_path = dir.path().join("runs");
+
+    let mut user = load_config()?.users.remove(0);
+    user.exec = Some(ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec![
+            "tests/exec-plugin.sh".to_string(),
+            "layer".to_string(),
+        ]),
+        env: Some(vec![ExecEnvVar {
+            name: "RUNS_OUT".to_string(),
+            value: runs_path.to_str().unwrap().to_string(),
+        }]),
+        install_hint: None,
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    });
+
+    let layer = AuthLayer::new(user, None);
+    let recorder = Recorder::default();
+    assert_eq!(
+        send(&layer, &recorder).await.as_deref(),
+        Some("Bearer token-for-layer")
+    );
+    assert_eq!(
+        send(&layer, &recorder).await.as_deref(),
+        Some("Bearer token-for-layer")
+    );
+    assert_eq!(count_runs(&runs_path), 1, "Expected the plugin to run once");
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_fails_the_request_when_the_credentials_are_unreadable() -> Result<()> {
+    let mut user = load_config()?.users.remove(0);
+    user.token_file = Some("/path/that/does/not/exist".into());
+
+    let recorder = Recorder::default();
+    let mut service = AuthLayer::new(user, None).layer(recorder.clone());
+    let result = service.call(Request::new(())).await;
+
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("/path/that/does/not/exist"),
+        "Expected the error to name the token file"
+    );
+    assert!(
+        recorder.headers.lock().unwrap().is_empty(),
+        "Expected the request not to be sent"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_connects_to_the_cluster_with_the_auth_layer() -> Result<()> {
+    let pki = Pki::generate();
+    let (server, requests) = start_https_server(&pki);
+
+    let dir = tempfile::tempdir()?;
+    let mut config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+    config.users.first_mut().unwrap().token = Some("some-token".to_string());
+
+    let connector = config.contexts[0].https_connector(&config)?;
+    let client = Client::builder(TokioExecutor::new()).build(connector);
+    let mut service = config.auth_layer("local")?.layer(client);
+
+    let request = Request::get(format!("{}/version", server))
+        .body(Empty::<bytes::Bytes>::new())
+        .unwrap();
+    let response = service.call(request).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let head = requests.recv().unwrap().to_lowercase();
+    assert!(
+        head.contains("authorization: bearer some-token"),
+        "Expected the bearer token to be sent, got {}",
+        head
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_sends_the_tls_server_name_as_sni() -> Result<()> {
+    let pki = Pki::generate();
+    let (server, requests) = start_https_server(&pki);
+    // The certificate of the server is only valid for localhost
+    let server = server.replace("localhost", "127.0.0.1");
+
+    let dir = tempfile::tempdir()?;
+    let mut config = write_config(
+        &dir.path().join("config"),
+        &server,
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+    config.clusters.first_mut().unwrap().tls_server_name = Some("localhost".to_string());
+
+    let connector = config.contexts[0].https_connector(&config)?;
+    let client = Client::builder(TokioExecutor::new()).build(connector);
+    let request = Request::get(format!("{}/version", server))
+        .body(Empty::<bytes::Bytes>::new())
+        .unwrap();
+    let response = client.request(request).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let head = requests.recv().unwrap().to_lowercase();
+    assert!(
+        head.contains("sni: localhost\r\n"),
+        "Expected the tls-server-name to be sent as SNI, got {}",
+        head
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_adds_the_impersonation_headers() -> Result<()> {
+    let mut user = load_config()?.users.remove(0);
+    user.token = Some("some-token".to_string());
+    user.impersonate = Some("jane".to_string());
+    user.impersonate_groups = Some(vec!["developers".to_string(), "auditors".to_string()]);
+
+    let recorder = Recorder::default();
+    send(&AuthLayer::new(user, None), &recorder).await;
+
+    let headers = recorder.headers.lock().unwrap();
+    let headers = headers.last().unwrap();
+    assert_eq!(headers.get("impersonate-user").unwrap(), "jane");
+    assert_eq!(
+        headers
+            .get_all("impersonate-group")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["developers", "auditors"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+pub async fn it_runs_the_exec_plugin_when_the_request_is_polled() -> Result<()> {
+    let mut user = load_config()?.users.remove(0);
+    user.exec = Some(ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec!["tests/exec-plugin.sh".to_string(), "slow".to_string()]),
+        env: Some(vec![ExecEnvVar {
+            name: "SLEEP".to_string(),
+            value: "1".to_string(),
+        }]),
+        install_hint: None,
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    });
+
+    let recorder = Recorder::default();
+    let mut service = AuthLayer::new(user, None).layer(recorder.clone());
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    let started = Instant::now();
+    let response = service.call(Request::new(()));
+    assert!(
+        started.elapsed() < Duration::from_millis(500),
+        "Expected call to return before the plugin is run"
+    );
+    response.await.unwrap();
+
+    let headers = recorder.headers.lock().unwrap();
+    assert_eq!(
+        headers.last().unwrap().get("authorization").unwrap(),
+        "Bearer token-for-slow"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn it_uses_the_client_certificate_of_the_exec_plugin() -> Result<()> {
+    let pki = Pki::generate();
+    let dir = tempfile::tempdir()?;
+    let mut config = write_config(
+        &dir.path().join("config"),
+        "https://localhost",
+        &pki,
+        Some(&pki.ca_pem),
+        false,
+    )?;
+
+    let exec_with = |cert: &str, key: &str| ExecConfig {
+        api_version: None,
+        command: "sh".to_string(),
+        args: Some(vec!["tests/exec-plugin.sh".to_string()]),
+        env: Some(vec![
+            ExecEnvVar {
+                name: "CERT_DATA".to_string(),
+                value: cert.replace('\n', "\\n"),
+            },
+            ExecEnvVar {
+                name: "KEY_DATA".to_string(),
+                value: key.replace('\n', "\\n"),
+            },
+        ]),
+        install_hint: None,
+        provide_cluster_info: false,
+        interactive_mode: Some(InteractiveMode::Never),
+        extra: Default::default(),
+    };
+    let user = config.users.first_mut().unwrap();
+    user.client_certificate_data = None;
+    user.client_key_data = None;
+
+    config.users.first_mut().unwrap().exec =
+        Some(exec_with(&pki.client_cert_pem, &pki.client_key_pem));
+    assert!(config.contexts[0].https_connector(&config).is_ok());
+
+    config.users.first_mut().unwrap().exec = Some(exec_with(&pki.client_cert_pem, "not-a-key"));
+    assert_eq!(
+        config.contexts[0]
+            .https_connector(&config)
+            .err()
+            .map(|e| e.to_string()),
+        Some("could not build tls configuration: invalid client key".to_string()),
+        "Expected the key of the plugin to be used"
+    );
+
+    Ok(())
+}